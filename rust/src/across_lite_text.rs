@@ -0,0 +1,468 @@
+//! Across Lite's text format, which constructors write by hand:
+//!
+//! ```text
+//! <ACROSS PUZZLE V2>
+//! <TITLE>
+//!     Title
+//! <SIZE>
+//!     3x3
+//! <GRID>
+//!     CAT
+//!     1GO
+//!     TE.
+//! <REBUS>
+//!     MARK;
+//!     1:AND:A
+//! <ACROSS>
+//!     Feline
+//! ...
+//! ```
+//!
+//! Rebus squares are written in the grid as a key defined in the <REBUS>
+//! section along with their full solution and a one letter fallback. When
+//! the section starts with MARK; lowercase letters in the grid are circled,
+//! as are rebus squares with a lowercase fallback.
+use crate::clues::{Clues, Direction};
+use crate::grid::Grid;
+use crate::markup::SquareFlags;
+use crate::square::Square;
+use crate::Puzzle;
+use anyhow::{Context, Error, Result};
+use std::collections::HashMap;
+
+const REBUS_KEYS: &str = "1234567890@#$%&*+?!";
+const MARK: &str = "MARK;";
+
+struct RebusEntry {
+    solution: String,
+    fallback: char,
+}
+
+/// Splits the text into its <SECTION>s, with the indentation of each line
+/// removed. Blank lines are kept, since an empty clue is written as one.
+fn sections(text: &str) -> Result<HashMap<String, Vec<&str>>> {
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty());
+
+    match lines.next() {
+        Some("<ACROSS PUZZLE>") | Some("<ACROSS PUZZLE V2>") => {}
+        first => {
+            return Err(Error::msg(format!(
+                "Expected text to start with <ACROSS PUZZLE>; received {:?}",
+                first.unwrap_or_default()
+            )))
+        }
+    }
+
+    let mut sections = HashMap::new();
+    let mut current = None;
+    for line in lines {
+        if line.starts_with('<') && line.ends_with('>') {
+            let name = line[1..line.len() - 1].to_string();
+            current = Some(sections.entry(name).or_insert_with(Vec::new));
+        } else if let Some(section) = current.as_mut() {
+            section.push(line);
+        } else if !line.is_empty() {
+            return Err(Error::msg(format!(
+                "Line {:?} is outside of a section",
+                line
+            )));
+        }
+    }
+
+    Ok(sections)
+}
+
+fn parse_rebus(lines: &[&str]) -> Result<(bool, HashMap<char, RebusEntry>)> {
+    let mut marked = false;
+    let mut table = HashMap::new();
+
+    for &line in lines.iter().filter(|line| !line.is_empty()) {
+        if line == MARK {
+            marked = true;
+            continue;
+        }
+
+        let mut parts = line.splitn(3, ':');
+        let (key, solution, fallback) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(key), Some(solution), Some(fallback)) => (key, solution, fallback),
+            _ => {
+                return Err(Error::msg(format!(
+                    "Expected rebus entries to look like 1:HEART:H; received {:?}",
+                    line
+                )))
+            }
+        };
+
+        let mut key_characters = key.chars();
+        let key = match (key_characters.next(), key_characters.next()) {
+            (Some(key), None) => key,
+            _ => {
+                return Err(Error::msg(format!(
+                    "Rebus key {:?} is not one character",
+                    key
+                )))
+            }
+        };
+
+        let fallback = fallback
+            .chars()
+            .next()
+            .ok_or_else(|| Error::msg(format!("Rebus {:?} has no one letter fallback", line)))?;
+
+        table.insert(
+            key,
+            RebusEntry {
+                solution: solution.to_string(),
+                fallback,
+            },
+        );
+    }
+
+    Ok((marked, table))
+}
+
+impl Puzzle {
+    /// Reads a puzzle written in Across Lite's text format
+    pub fn from_across_lite_text(text: &str) -> Result<Puzzle> {
+        let sections = sections(text)?;
+        let section = |name: &str| sections.get(name).map(Vec::as_slice).unwrap_or_default();
+        let joined = |name: &str| section(name).join("\n").trim().to_string();
+
+        let rows = section("GRID")
+            .iter()
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>();
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+
+        if let Some(size) = section("SIZE").iter().find(|size| !size.is_empty()) {
+            let expected = format!("{}x{}", width, height);
+            if size.to_lowercase() != expected {
+                return Err(Error::msg(format!(
+                    "<SIZE> is {} but the grid is {}",
+                    size, expected
+                )));
+            }
+        }
+
+        let (marked, rebus_table) = parse_rebus(section("REBUS"))?;
+
+        let mut solution = String::new();
+        let mut rebus = vec![];
+        let mut circles = vec![];
+        for (row, line) in rows.iter().enumerate() {
+            if line.chars().count() != width {
+                return Err(Error::msg(format!(
+                    "Row {} of the grid has {} squares; expected {}",
+                    row + 1,
+                    line.chars().count(),
+                    width
+                )));
+            }
+
+            for (column, square) in line.chars().enumerate() {
+                let index = row * width + column;
+
+                if let Some(entry) = rebus_table.get(&square) {
+                    if marked && entry.fallback.is_lowercase() {
+                        circles.push(index);
+                    }
+                    solution.push(entry.fallback.to_ascii_uppercase());
+                    rebus.push((index, entry.solution.clone()));
+                } else if Square::is_black_square(square) {
+                    solution.push(Square::black());
+                } else if square.is_alphabetic() {
+                    if marked && square.is_lowercase() {
+                        circles.push(index);
+                    }
+                    solution.extend(square.to_uppercase());
+                } else {
+                    return Err(Error::msg(format!(
+                        "Square {:?} in row {} isn't a letter, black square or rebus key",
+                        square,
+                        row + 1
+                    )));
+                }
+            }
+        }
+
+        // match the separate across and down lists up with the grid's
+        // numbering. Blank lines after the last clue are only spacing, but
+        // the grid decides where the clues end, as the last may be empty.
+        let numbering = Clues::numbering(Grid::new(&solution, &solution, width, height));
+        let mut across = section("ACROSS").iter();
+        let mut down = section("DOWN").iter();
+
+        let mut all_clues = vec![];
+        for (direction, clue) in numbering.in_puz_order() {
+            let text = match direction {
+                Direction::Across => across.next(),
+                Direction::Down => down.next(),
+            };
+
+            all_clues.push(
+                text.ok_or_else(|| {
+                    Error::msg(format!(
+                        "The grid has {} across and {} down clues but fewer were provided; ran out at {} {:?}",
+                        numbering.across.len(),
+                        numbering.down.len(),
+                        clue.clue_number,
+                        direction
+                    ))
+                })?
+                .to_string(),
+            );
+        }
+
+        if across.chain(down).any(|line| !line.is_empty()) {
+            return Err(Error::msg(format!(
+                "The grid has {} across and {} down clues but more were provided",
+                numbering.across.len(),
+                numbering.down.len()
+            )));
+        }
+
+        let mut puzzle = Puzzle::new(width, height, &solution, all_clues)
+            .context("Failed to build puzzle from text")?;
+
+        puzzle.title = joined("TITLE");
+        puzzle.author = joined("AUTHOR");
+        puzzle.copyright = joined("COPYRIGHT");
+        puzzle.notes = joined("NOTEPAD");
+
+        for (index, answer) in rebus {
+            puzzle.rebus.set(index, &answer)?;
+        }
+
+        for index in circles {
+            let mut flags = SquareFlags::default();
+            flags.set_circled(true);
+            puzzle.markup.set(index, flags)?;
+        }
+
+        puzzle.upgrade_version_for_text();
+        puzzle.refresh_checksums()?;
+
+        Ok(puzzle)
+    }
+
+    /// Writes the puzzle in Across Lite's text format. The solver's fill
+    /// isn't part of the format and is dropped.
+    pub fn to_across_lite_text(&self) -> Result<String> {
+        self.ensure_unlocked("they can be written as text")?;
+
+        let grid = self.grid();
+        let mut rebus_keys: Vec<(String, char, char)> = vec![];
+        let mut has_circles = false;
+        let mut rows = vec![];
+
+        for row in 0..self.height() {
+            let mut line = String::new();
+
+            for column in 0..self.width() {
+                let index = row * self.width() + column;
+                let square = grid
+                    .get_solution_character(index)
+                    .ok_or_else(|| Error::msg(format!("Square {} has no solution", index)))?;
+
+                if let Some(answer) = self.rebus.get(index) {
+                    // circled rebus squares are marked by a lowercase
+                    // fallback, so they need a key of their own
+                    let fallback = if grid.get_flags(index).is_circled() {
+                        has_circles = true;
+                        square.to_lowercase().next().unwrap_or(square)
+                    } else {
+                        square
+                    };
+
+                    let key = match rebus_keys.iter().find(|(existing, _, existing_fallback)| {
+                        existing == answer && *existing_fallback == fallback
+                    }) {
+                        Some(&(_, key, _)) => key,
+                        None => {
+                            let key =
+                                REBUS_KEYS.chars().nth(rebus_keys.len()).ok_or_else(|| {
+                                    Error::msg(
+                                        "Too many distinct rebus squares for the text format",
+                                    )
+                                })?;
+                            rebus_keys.push((answer.to_string(), key, fallback));
+                            key
+                        }
+                    };
+
+                    line.push(key);
+                } else if grid.get_flags(index).is_circled() && !Square::is_black_square(square) {
+                    has_circles = true;
+                    line.extend(square.to_lowercase());
+                } else {
+                    line.push(square);
+                }
+            }
+
+            rows.push(line);
+        }
+
+        let mut text = String::from("<ACROSS PUZZLE V2>\n");
+        let mut section = |name: &str, lines: &[String]| {
+            text.push_str(&format!("<{}>\n", name));
+            for line in lines {
+                text.push_str(&format!("\t{}\n", line));
+            }
+        };
+        let lines = |text: &str| text.lines().map(String::from).collect::<Vec<_>>();
+
+        section("TITLE", &lines(&self.title));
+        section("AUTHOR", &lines(&self.author));
+        section("COPYRIGHT", &lines(&self.copyright));
+        section("SIZE", &[format!("{}x{}", self.width(), self.height())]);
+        section("GRID", &rows);
+
+        if has_circles || !rebus_keys.is_empty() {
+            let mut rebus = vec![];
+            if has_circles {
+                rebus.push(MARK.to_string());
+            }
+
+            for (answer, key, fallback) in &rebus_keys {
+                rebus.push(format!("{}:{}:{}", key, answer, fallback));
+            }
+
+            section("REBUS", &rebus);
+        }
+
+        let clue_texts = |clues: &[crate::Clue]| {
+            clues
+                .iter()
+                .map(|clue| clue.text.clone())
+                .collect::<Vec<_>>()
+        };
+        section("ACROSS", &clue_texts(&self.clues.across));
+        section("DOWN", &clue_texts(&self.clues.down));
+
+        if !self.notes.is_empty() {
+            section("NOTEPAD", &lines(&self.notes));
+        }
+
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::read_fixture;
+    use crate::Puzzle;
+    use anyhow::Result;
+
+    /// zack.puz in the text format, edited as if by hand
+    fn zack_text(edits: &[(&str, &str)]) -> Result<String> {
+        let mut text = read_fixture("zack.puz")?.to_across_lite_text()?;
+        for (from, to) in edits {
+            assert!(text.contains(from), "{:?} isn't in the text", from);
+            text = text.replace(from, to);
+        }
+
+        Ok(text)
+    }
+
+    #[test]
+    fn test_across_lite_text_round_trip() -> Result<()> {
+        let puzzle = read_fixture("nyt_rebus_with_notes_and_shape.puz")?;
+        let reparsed = Puzzle::from_across_lite_text(&puzzle.to_across_lite_text()?)?;
+
+        assert_eq!(reparsed.title, puzzle.title);
+        // spaces around a line can't be told from its indentation, and
+        // lines end however the text does
+        assert_eq!(reparsed.author, puzzle.author.trim());
+        assert_eq!(reparsed.copyright, puzzle.copyright.trim());
+        assert_eq!(reparsed.notes, puzzle.notes.replace("\r\n", "\n").trim());
+        assert_eq!(reparsed.solution, puzzle.solution);
+        assert_eq!(reparsed.all_clues, puzzle.all_clues);
+        assert_eq!(reparsed.rebus.get(22), Some("STAR"));
+        assert_eq!(reparsed.markup, puzzle.markup);
+
+        // an empty clue, and a circled rebus square next to one that isn't
+        let mut puzzle = read_fixture("zack.puz")?;
+        puzzle.rebus.set(5, "RU")?;
+        puzzle.rebus.set(7, "RU")?;
+        let mut flags = puzzle.grid().get_flags(5);
+        flags.set_circled(true);
+        puzzle.markup.set(5, flags)?;
+        puzzle.all_clues[1].clear();
+        puzzle.clues.down[0].text.clear();
+
+        let text = puzzle.to_across_lite_text()?;
+        assert!(text.contains("<DOWN>\n\t\n\tHarold's"));
+        let reparsed = Puzzle::from_across_lite_text(&text)?;
+        assert_eq!(reparsed.all_clues, puzzle.all_clues);
+        assert_eq!(reparsed.markup, puzzle.markup);
+        assert_eq!(reparsed.rebus.get(5), Some("RU"));
+        assert_eq!(reparsed.rebus.get(7), Some("RU"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_last_clues() -> Result<()> {
+        // the last clue of each list is written as a blank line, which
+        // mustn't be mistaken for spacing before the next section
+        let mut puzzle = read_fixture("zack.puz")?;
+        for index in [6, 9] {
+            puzzle.all_clues[index].clear();
+        }
+        puzzle.clues.across[4].text.clear();
+        puzzle.clues.down[4].text.clear();
+
+        let text = puzzle.to_across_lite_text()?;
+        assert!(text.ends_with(&format!("\t{}\n\t\n", puzzle.all_clues[4])));
+        let reparsed = Puzzle::from_across_lite_text(&text)?;
+        assert_eq!(reparsed.all_clues, puzzle.all_clues);
+
+        // blank lines after the clues are still only spacing
+        let spaced = text.replace("<DOWN>", "\n\n<DOWN>") + "\n\n";
+        let reparsed = Puzzle::from_across_lite_text(&spaced)?;
+        assert_eq!(reparsed.all_clues, puzzle.all_clues);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hand_written_text() -> Result<()> {
+        // the first version of the format, indented with spaces, with a
+        // rebus but without MARK; so lowercase letters are just letters
+        let puzzle = Puzzle::from_across_lite_text(&zack_text(&[
+            ("<ACROSS PUZZLE V2>", "<ACROSS PUZZLE>"),
+            ("\tXKCD.\n", "    1KCD.\n"),
+            ("\tRURAL\n", "    rural\n"),
+            ("<ACROSS>", "<REBUS>\n    1:XRAY:x\n<ACROSS>"),
+            ("\tFallen", "  Fallen"),
+        ])?)?;
+
+        assert_eq!(puzzle.solution, "XKCD.RURALAMALEYATES.REKT");
+        assert_eq!(puzzle.rebus.get(0), Some("XRAY"));
+        assert!(puzzle.markup.is_empty());
+        assert_eq!(puzzle.clues.across[4].text, "Fallen in Fortnite, say");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_text() -> Result<()> {
+        let missing = zack_text(&[("\tIn case\n", "")])?;
+        assert!(Puzzle::from_across_lite_text(&missing).is_err());
+
+        let extra = zack_text(&[("\tIn case\n", "\tIn case\n\tToo\n")])?;
+        assert!(Puzzle::from_across_lite_text(&extra).is_err());
+
+        let wrong_size = zack_text(&[("5x5", "6x5")])?;
+        assert!(Puzzle::from_across_lite_text(&wrong_size).is_err());
+
+        let unknown_key = zack_text(&[("\tXKCD.", "\t1KCD.")])?;
+        assert!(Puzzle::from_across_lite_text(&unknown_key).is_err());
+
+        Ok(())
+    }
+}
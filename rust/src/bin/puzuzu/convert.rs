@@ -0,0 +1,413 @@
+use anyhow::{Context, Error, Result};
+use puzuzu::{HtmlOptions, Puzzle, SvgOptions};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The magic string in a puz file's header, and the null after it
+const PUZ_MAGIC: &[u8] = b"ACROSS&DOWN\0";
+/// Bytes in a puz header, which starts with a 2 byte checksum before the
+/// magic string
+const PUZ_HEADER_SIZE: usize = 52;
+
+/// Whether the data has a puz header in it. Some puz files have junk
+/// before the header, so it can be anywhere, but text formats can't hold
+/// the null after the magic string.
+fn has_puz_header(data: &[u8]) -> bool {
+    data.windows(PUZ_MAGIC.len())
+        .position(|window| window == PUZ_MAGIC)
+        .is_some_and(|index| index >= 2 && index - 2 + PUZ_HEADER_SIZE <= data.len())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Puz,
+    Ipuz,
+    Jpz,
+    AcrossLiteText,
+    Xd,
+    Exolve,
+    Svg,
+    Html,
+}
+
+impl Format {
+    const ALL: [Format; 8] = [
+        Format::Puz,
+        Format::Ipuz,
+        Format::Jpz,
+        Format::AcrossLiteText,
+        Format::Xd,
+        Format::Exolve,
+        Format::Svg,
+        Format::Html,
+    ];
+
+    /// The name used with --to, which is also the file extension
+    fn name(self) -> &'static str {
+        match self {
+            Format::Puz => "puz",
+            Format::Ipuz => "ipuz",
+            Format::Jpz => "jpz",
+            Format::AcrossLiteText => "txt",
+            Format::Xd => "xd",
+            Format::Exolve => "exolve",
+            Format::Svg => "svg",
+            Format::Html => "html",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Format> {
+        let name = name.to_ascii_lowercase();
+        Format::ALL
+            .iter()
+            .copied()
+            .find(|format| format.name() == name)
+    }
+
+    fn from_path(path: &Path) -> Option<Format> {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Format::from_name)
+    }
+
+    /// Whether puzzles can be read from this format, not just written to it
+    fn is_readable(self) -> bool {
+        matches!(
+            self,
+            Format::Puz | Format::Ipuz | Format::Jpz | Format::AcrossLiteText | Format::Xd
+        )
+    }
+
+    /// Works out the format of a file from its contents where they have a
+    /// recognizable signature, falling back to its extension
+    fn detect(path: &Path, data: &[u8]) -> Result<Format> {
+        let text = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+        let start = text.trim_start_matches('\u{feff}').trim_start();
+
+        let sniffed = if has_puz_header(data) {
+            Some(Format::Puz)
+        } else if data.starts_with(b"PK\x03\x04") {
+            // jpz files are often zipped
+            Some(Format::Jpz)
+        } else if start.starts_with("<ACROSS PUZZLE") {
+            Some(Format::AcrossLiteText)
+        } else if start.starts_with('{') {
+            Some(Format::Ipuz)
+        } else if start.starts_with('<') && text.contains("crossword-compiler") {
+            Some(Format::Jpz)
+        } else {
+            None
+        };
+
+        sniffed
+            .or_else(|| Format::from_path(path))
+            .filter(|format| format.is_readable())
+            .ok_or_else(|| Error::msg(format!("Can't tell what format {} is in", path.display())))
+    }
+
+    fn read(self, data: Vec<u8>) -> Result<Puzzle> {
+        let text = |data: Vec<u8>| {
+            String::from_utf8(data).context(format!("{} files must be UTF-8", self.name()))
+        };
+
+        match self {
+            Format::Puz => Ok(Puzzle::from_puz(data)?),
+            Format::Ipuz => Puzzle::from_ipuz(&text(data)?),
+            Format::Jpz => Puzzle::from_jpz(&data),
+            Format::AcrossLiteText => Puzzle::from_across_lite_text(&text(data)?),
+            Format::Xd => Puzzle::from_xd(&text(data)?),
+            Format::Exolve | Format::Svg | Format::Html => Err(Error::msg(format!(
+                "Puzzles can't be read from {}",
+                self.name()
+            ))),
+        }
+    }
+
+    fn write(self, puzzle: &Puzzle) -> Result<Vec<u8>> {
+        let text = match self {
+            Format::Puz => return puzzle.to_puz(),
+            Format::Ipuz => puzzle.to_ipuz()?,
+            Format::Jpz => puzzle.to_jpz()?,
+            Format::AcrossLiteText => puzzle.to_across_lite_text()?,
+            Format::Xd => puzzle.to_xd()?,
+            Format::Exolve => puzzle.to_exolve()?,
+            Format::Svg => puzzle.to_svg(&SvgOptions::default())?,
+            Format::Html => puzzle.to_html(&HtmlOptions::default())?,
+        };
+
+        Ok(text.into_bytes())
+    }
+}
+
+/// Expands the inputs into the files to convert: directories are replaced
+/// by the puzzles directly inside them and patterns such as `*.puz` by the
+/// files they match, for shells that don't expand them
+fn expand_inputs(inputs: &[String]) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            let mut entries = std::fs::read_dir(path)
+                .context(format!("Failed to read directory {}", input))?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()
+                .context(format!("Failed to read directory {}", input))?;
+            entries.retain(|entry| {
+                entry.is_file() && Format::from_path(entry).is_some_and(Format::is_readable)
+            });
+            entries.sort();
+            paths.extend(entries);
+        } else if !path.exists() && input.contains(['*', '?', '[']) {
+            let matches = glob::glob(input)
+                .context(format!("Invalid pattern {}", input))?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.is_file())
+                .collect::<Vec<_>>();
+            if matches.is_empty() {
+                return Err(Error::msg(format!("No files match {}", input)));
+            }
+            paths.extend(matches);
+        } else {
+            paths.push(path.to_path_buf());
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Where a converted file is written: the output itself when converting a
+/// single file to a path that isn't a directory, otherwise next to the
+/// input or in the output directory, with the new format's extension
+fn output_path(input: &Path, output: Option<&Path>, single: bool, format: Format) -> PathBuf {
+    match output {
+        Some(output) if single && !output.is_dir() => output.to_path_buf(),
+        Some(directory) => directory
+            .join(input.file_name().unwrap_or_default())
+            .with_extension(format.name()),
+        None => input.with_extension(format.name()),
+    }
+}
+
+/// The output path of each input, refusing to write two inputs to the same
+/// file, as `a/x.puz` and `b/x.puz` would be in one output directory
+fn output_paths(
+    inputs: &[PathBuf],
+    output: Option<&Path>,
+    single: bool,
+    format: Format,
+) -> Result<Vec<PathBuf>> {
+    let mut sources = HashMap::new();
+    inputs
+        .iter()
+        .map(|input| {
+            let destination = output_path(input, output, single, format);
+            match sources.insert(destination.clone(), input) {
+                Some(other) => Err(Error::msg(format!(
+                    "{} and {} would both be converted to {}",
+                    other.display(),
+                    input.display(),
+                    destination.display()
+                ))),
+                None => Ok(destination),
+            }
+        })
+        .collect()
+}
+
+fn convert_file(input: &Path, output: &Path, format: Format) -> Result<()> {
+    if input == output {
+        return Err(Error::msg("Converting would overwrite the input"));
+    }
+
+    let data = std::fs::read(input).context("Failed to read file")?;
+    let puzzle = Format::detect(input, &data)?
+        .read(data)
+        .context("Failed to parse puzzle")?;
+    let converted = format
+        .write(&puzzle)
+        .context(format!("Failed to convert puzzle to {}", format.name()))?;
+
+    std::fs::write(output, converted).context(format!("Failed to write {}", output.display()))
+}
+
+/// `puzuzu convert [--to format] [-o output] inputs...`: converts each input
+/// and prints a line per file, returning whether they all succeeded
+pub fn run(args: &[String]) -> Result<bool> {
+    let mut format = None;
+    let mut output = None;
+    let mut inputs = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-t" | "--to" => format = Some(args.next().unwrap_or_else(|| crate::usage())),
+            "-o" | "--output" => {
+                output = Some(PathBuf::from(args.next().unwrap_or_else(|| crate::usage())))
+            }
+            flag if flag.starts_with('-') => crate::usage(),
+            input => inputs.push(input.to_string()),
+        }
+    }
+    if inputs.is_empty() {
+        crate::usage();
+    }
+
+    let paths = expand_inputs(&inputs)?;
+    if paths.is_empty() {
+        return Err(Error::msg("No puzzles to convert"));
+    }
+    let single = paths.len() == 1 && !output.as_deref().is_some_and(Path::is_dir);
+
+    let format = match format {
+        Some(name) => Format::from_name(name).ok_or_else(|| {
+            Error::msg(format!(
+                "Unknown format {}; expected one of {}",
+                name,
+                Format::ALL.map(Format::name).join(", ")
+            ))
+        })?,
+        None => output
+            .as_deref()
+            .filter(|_| single)
+            .and_then(Format::from_path)
+            .ok_or_else(|| Error::msg("--to is required unless the output has an extension"))?,
+    };
+
+    if let Some(directory) = output.as_deref().filter(|_| !single) {
+        std::fs::create_dir_all(directory)
+            .context(format!("Failed to create {}", directory.display()))?;
+    }
+
+    let destinations = output_paths(&paths, output.as_deref(), single, format)?;
+
+    let mut failures = 0;
+    for (input, destination) in paths.iter().zip(&destinations) {
+        match convert_file(input, destination, format) {
+            Ok(()) => println!("ok      {} -> {}", input.display(), destination.display()),
+            Err(error) => {
+                failures += 1;
+                println!("failed  {}: {:#}", input.display(), error);
+            }
+        }
+    }
+
+    println!("{} converted, {} failed", paths.len() - failures, failures);
+
+    Ok(failures == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert_file, output_path, output_paths, Format};
+    use anyhow::Result;
+    use puzuzu::Puzzle;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_detect() -> Result<()> {
+        let washpost = Path::new("../test_files/washpost.puz");
+        let data = std::fs::read(washpost)?;
+        assert_eq!(Format::detect(washpost, &data)?, Format::Puz);
+        // the contents win over the extension
+        assert_eq!(Format::detect(Path::new("puzzle.xd"), &data)?, Format::Puz);
+
+        let puzzle = Puzzle::from_puz(data)?;
+        for format in [Format::Ipuz, Format::Jpz, Format::AcrossLiteText] {
+            let written = format.write(&puzzle)?;
+            assert_eq!(
+                Format::detect(Path::new("puzzle"), &written)?,
+                format,
+                "{} wasn't detected",
+                format.name()
+            );
+        }
+
+        let xd = Format::Xd.write(&puzzle)?;
+        assert_eq!(Format::detect(Path::new("puzzle.xd"), &xd)?, Format::Xd);
+        assert!(Format::detect(Path::new("puzzle"), &xd).is_err());
+        assert!(Format::detect(Path::new("puzzle.svg"), b"<svg>").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_misleading_contents() -> Result<()> {
+        let ipuz = Path::new("puzzle.ipuz");
+        assert_eq!(
+            Format::detect(ipuz, br#"{"notes":"ACROSS&DOWN"}"#)?,
+            Format::Ipuz
+        );
+
+        let mut puzzle = Puzzle::from_puz(std::fs::read("../test_files/washpost.puz")?)?;
+        puzzle.author = "ACROSS&DOWN".repeat(10);
+        for format in [Format::Ipuz, Format::Jpz, Format::AcrossLiteText] {
+            let written = format.write(&puzzle)?;
+            assert_eq!(Format::detect(Path::new("puzzle"), &written)?, format);
+        }
+
+        // too close to the start, or too short, for a whole header
+        assert_eq!(Format::detect(ipuz, b"ACROSS&DOWN\0")?, Format::Ipuz);
+        let mut header = vec![0; 2];
+        header.extend_from_slice(b"ACROSS&DOWN\0");
+        assert_eq!(Format::detect(ipuz, &header)?, Format::Ipuz);
+        header.resize(52, 0);
+        assert_eq!(Format::detect(ipuz, &header)?, Format::Puz);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_path() {
+        let input = Path::new("in/puzzle.puz");
+        assert_eq!(
+            output_path(input, None, true, Format::Xd),
+            Path::new("in/puzzle.xd")
+        );
+        assert_eq!(
+            output_path(input, Some(Path::new("out.ipuz")), true, Format::Ipuz),
+            Path::new("out.ipuz")
+        );
+        assert_eq!(
+            output_path(input, Some(Path::new("out")), false, Format::Jpz),
+            Path::new("out/puzzle.jpz")
+        );
+    }
+
+    #[test]
+    fn test_output_path_collisions() -> Result<()> {
+        let inputs = [PathBuf::from("a/x.puz"), PathBuf::from("b/x.puz")];
+        assert_eq!(
+            output_paths(&inputs, None, false, Format::Xd)?,
+            [Path::new("a/x.xd"), Path::new("b/x.xd")]
+        );
+
+        let error = output_paths(&inputs, Some(Path::new("out")), false, Format::Xd).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("a/x.puz and b/x.puz would both be converted to"));
+
+        // the same name in the same directory, in two formats
+        let inputs = [PathBuf::from("a/x.puz"), PathBuf::from("a/x.ipuz")];
+        assert!(output_paths(&inputs, None, false, Format::Xd).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_file() -> Result<()> {
+        let input = Path::new("../test_files/zack.puz");
+        let output = std::env::temp_dir().join(format!("puzuzu-{}-zack.xd", std::process::id()));
+
+        convert_file(input, &output, Format::Xd)?;
+        let converted = Puzzle::from_xd(&std::fs::read_to_string(&output)?)?;
+        std::fs::remove_file(&output)?;
+
+        let original = Puzzle::from_puz(std::fs::read(input)?)?;
+        assert_eq!(converted.solution, original.solution);
+        assert_eq!(converted.title, original.title);
+
+        assert!(convert_file(input, input, Format::Puz).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use puzuzu::{Checksum, ChecksumVerification, ParseOptions, Puzzle};
+use std::fmt::Write;
+
+/// Lists the checksums that were repaired, with their old and new values
+fn describe(path: &str, changed: &[Checksum]) -> String {
+    if changed.is_empty() {
+        return format!("{}: checksums are correct\n", path);
+    }
+
+    let mut text = format!("{}: repaired {} checksums\n", path, changed.len());
+    for checksum in changed {
+        // writing to a String can't fail
+        let _ = writeln!(
+            text,
+            "  {:<9}{:#x} -> {:#x}",
+            checksum.name, checksum.stored, checksum.calculated
+        );
+    }
+
+    text
+}
+
+/// Repairs the checksums of the file at `path`, writing it to `output` if
+/// any changed, and describes what was done
+fn fix_file(path: &str, output: &str) -> Result<String> {
+    let data = std::fs::read(path).context(format!("Failed to read {}", path))?;
+
+    // keep the file's checksums until they're repaired, so nothing else
+    // about the file changes
+    let options = ParseOptions {
+        verify_checksums: ChecksumVerification::Ignore,
+        repair_checksums: false,
+    };
+    let mut puzzle = Puzzle::from_puz_with_options(data, &options)
+        .context(format!("Failed to parse {}", path))?;
+
+    let changed = puzzle
+        .repair_checksums()
+        .context(format!("Failed to calculate checksums of {}", path))?;
+
+    if !changed.is_empty() || output != path {
+        let data = puzzle
+            .to_puz()
+            .context(format!("Failed to write puzzle {}", path))?;
+        std::fs::write(output, data).context(format!("Failed to write {}", output))?;
+    }
+
+    Ok(describe(path, &changed))
+}
+
+/// `puzuzu fix [-o output] paths...`: rewrites each file with correct
+/// checksums, in place unless an output is given for a single file, and
+/// returns whether every file could be fixed
+pub fn run(args: &[String]) -> Result<bool> {
+    let (output, paths) = match args {
+        [flag, output, path] if flag == "-o" || flag == "--output" => (Some(output), vec![path]),
+        [path, flag, output] if flag == "-o" || flag == "--output" => (Some(output), vec![path]),
+        paths => (None, paths.iter().collect()),
+    };
+    if paths.is_empty() || paths.iter().any(|path| path.starts_with('-')) {
+        crate::usage();
+    }
+
+    let mut success = true;
+    for path in paths {
+        match fix_file(path, output.unwrap_or(path)) {
+            Ok(report) => print!("{}", report),
+            Err(error) => {
+                success = false;
+                println!("{}: {:#}", path, error);
+            }
+        }
+    }
+
+    Ok(success)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe, fix_file};
+    use anyhow::Result;
+    use puzuzu::{Checksum, Puzzle};
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe("puzzle.puz", &[]),
+            "puzzle.puz: checksums are correct\n"
+        );
+        assert_eq!(
+            describe(
+                "puzzle.puz",
+                &[Checksum {
+                    name: "global".to_string(),
+                    stored: 0x4938,
+                    calculated: 0x4c66,
+                }]
+            ),
+            "puzzle.puz: repaired 1 checksums\n  global   0x4938 -> 0x4c66\n"
+        );
+    }
+
+    #[test]
+    fn test_fix_file() -> Result<()> {
+        let mut data = std::fs::read("../test_files/zack.puz")?;
+        // the global checksum is the first two bytes
+        data[0] ^= 1;
+        let path = std::env::temp_dir().join(format!("puzuzu-{}-fix.puz", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, &data)?;
+
+        let report = fix_file(path, path)?;
+        let fixed = std::fs::read(path)?;
+        let again = fix_file(path, path)?;
+        std::fs::remove_file(path)?;
+
+        assert!(report.contains("repaired 1 checksums"));
+        assert!(report.contains("  global "));
+        assert!(fixed == std::fs::read("../test_files/zack.puz")?);
+        assert!(Puzzle::from_puz(fixed).is_ok());
+        assert!(again.ends_with("checksums are correct\n"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{Context, Result};
+use puzuzu::{Checksum, Puzzle};
+use serde_json::json;
+use std::fmt::Write;
+
+/// Describes the header, checksums and extensions of a puzzle for people
+fn describe(puzzle: &Puzzle, checksums: &[Checksum]) -> String {
+    let header = puzzle.header();
+    let mut text = String::new();
+
+    // writing to a String can't fail
+    let field = |text: &mut String, name: &str, value: String| {
+        let _ = writeln!(text, "{:<20}{}", name, value);
+    };
+    field(&mut text, "Version", header.file_version.clone());
+    field(
+        &mut text,
+        "Size",
+        format!("{}x{}", header.width, header.height),
+    );
+    field(&mut text, "Clues", header.clue_count.to_string());
+    field(&mut text, "Type", format!("{:?}", header.puzzle_type));
+    field(
+        &mut text,
+        "Solution",
+        format!("{:?}", header.solution_state),
+    );
+    field(
+        &mut text,
+        "Scrambled checksum",
+        format!("{:#06x}", header.scrambled_checksum),
+    );
+
+    text.push_str("\nChecksums\n");
+    for checksum in checksums {
+        let status = if checksum.is_valid() {
+            "ok".to_string()
+        } else {
+            format!("MISMATCH, calculated {:#x}", checksum.calculated)
+        };
+        let _ = writeln!(
+            text,
+            "  {:<18}{:<20}{}",
+            checksum.name,
+            format!("{:#x}", checksum.stored),
+            status
+        );
+    }
+
+    text.push_str("\nExtensions\n");
+    if puzzle.extensions().is_empty() {
+        text.push_str("  none\n");
+    }
+    for extension in puzzle.extensions() {
+        let _ = writeln!(
+            text,
+            "  {:<18}{} bytes",
+            String::from_utf8_lossy(&extension.code),
+            extension.bytes.len()
+        );
+    }
+
+    text
+}
+
+/// Describes the header, checksums and extensions of a puzzle for scripts
+fn to_json(puzzle: &Puzzle, checksums: &[Checksum]) -> serde_json::Value {
+    let header = puzzle.header();
+
+    json!({
+        "version": header.file_version,
+        "width": header.width,
+        "height": header.height,
+        "clueCount": header.clue_count,
+        "puzzleType": format!("{:?}", header.puzzle_type),
+        "solutionState": format!("{:?}", header.solution_state),
+        "scrambledChecksum": header.scrambled_checksum,
+        "checksums": checksums
+            .iter()
+            .map(|checksum| json!({
+                "name": checksum.name,
+                "stored": checksum.stored,
+                "calculated": checksum.calculated,
+                "valid": checksum.is_valid(),
+            }))
+            .collect::<Vec<_>>(),
+        "extensions": puzzle
+            .extensions()
+            .iter()
+            .map(|extension| json!({
+                "code": String::from_utf8_lossy(&extension.code),
+                "length": extension.bytes.len(),
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// `puzuzu info [--json] path`: prints what's in the file's header and
+/// returns whether all of its checksums match
+pub fn run(args: &[String]) -> Result<bool> {
+    let (json, path) = match args {
+        [flag, path] if flag == "--json" => (true, path),
+        [path, flag] if flag == "--json" => (true, path),
+        [path] if !path.starts_with('-') => (false, path),
+        _ => crate::usage(),
+    };
+
+    let data = std::fs::read(path).context(format!("Failed to read {}", path))?;
+    // checksums are reported rather than refused, so damaged files can be
+    // inspected
+    let puzzle = Puzzle::from_puz_unverified(data).context(format!("Failed to parse {}", path))?;
+    let checksums = puzzle
+        .checksums()
+        .context(format!("Failed to calculate checksums of {}", path))?;
+
+    if json {
+        println!("{:#}", to_json(&puzzle, &checksums));
+    } else {
+        print!("{}", describe(&puzzle, &checksums));
+    }
+
+    Ok(checksums.iter().all(Checksum::is_valid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe, to_json};
+    use anyhow::Result;
+    use puzuzu::Puzzle;
+
+    #[test]
+    fn test_describe() -> Result<()> {
+        let puzzle = Puzzle::from_puz(std::fs::read("../test_files/nyt_locked.puz")?)?;
+        let checksums = puzzle.checksums()?;
+
+        let text = describe(&puzzle, &checksums);
+        assert!(text.contains("Version             1.3\n"));
+        assert!(text.contains("Size                15x15\n"));
+        assert!(text.contains("Solution            Locked\n"));
+        assert!(!text.contains("MISMATCH"));
+
+        let json = to_json(&puzzle, &checksums);
+        assert_eq!(json["width"], 15);
+        assert_eq!(json["solutionState"], "Locked");
+        assert_eq!(json["checksums"][0]["name"], "global");
+        assert_eq!(json["checksums"][0]["valid"], true);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe_damaged() -> Result<()> {
+        let mut data = std::fs::read("../test_files/nyt_weekday_with_notes.puz")?;
+        // the first letter of the solution, just after the 52 byte header
+        data[52] ^= 1;
+        let puzzle = Puzzle::from_puz_unverified(data)?;
+        let checksums = puzzle.checksums()?;
+
+        assert!(describe(&puzzle, &checksums).contains("MISMATCH"));
+        assert_eq!(to_json(&puzzle, &checksums)["checksums"][0]["valid"], false);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+//! puzuzu: inspect and convert crossword files and solve them in the terminal
+mod convert;
+mod fix;
+mod info;
+#[cfg(feature = "tui")]
+mod tui;
+mod validate;
+
+use anyhow::Result;
+
+const USAGE: &str = "Usage:
+  puzuzu [-f] path/to/puz-file.puz            solve the puzzle in the terminal
+  puzuzu info [--json] path/to/puz-file.puz   show the header, checksums and extensions
+  puzuzu convert [--to format] [-o output] inputs...
+                                              convert files, directories or patterns to
+                                              puz, ipuz, jpz, txt, xd, exolve, svg or html
+  puzuzu validate [--json] paths...           list errors and warnings in .puz files
+  puzuzu fix [-o output] paths...             rewrite .puz files with correct checksums";
+
+/// Prints the usage and exits, for when the arguments don't make sense
+pub fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+fn main() -> Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    match args.first().map(String::as_str) {
+        Some("info") => exit_unless(info::run(&args[1..])?),
+        Some("convert") => exit_unless(convert::run(&args[1..])?),
+        Some("validate") => exit_unless(validate::run(&args[1..])?),
+        Some("fix") => exit_unless(fix::run(&args[1..])?),
+        _ => solve(&args),
+    }
+}
+
+/// Exits with a failure status if a command found problems it reported
+fn exit_unless(success: bool) -> Result<()> {
+    if !success {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tui")]
+fn solve(args: &[String]) -> Result<()> {
+    use anyhow::Context;
+    use puzuzu::Puzzle;
+
+    // -f is accepted for compatibility with the TypeScript CLI
+    let path = match args {
+        [flag, path] if flag == "-f" || flag == "--file" => path,
+        [path] if !path.starts_with('-') => path,
+        _ => usage(),
+    };
+
+    let data = std::fs::read(path).context(format!("Failed to read {}", path))?;
+    let puzzle = Puzzle::from_puz(data).context(format!("Failed to parse {}", path))?;
+
+    tui::run(puzzle, path)
+}
+
+#[cfg(not(feature = "tui"))]
+fn solve(_args: &[String]) -> Result<()> {
+    Err(anyhow::Error::msg(
+        "puzuzu was built without the tui feature, so it can't solve puzzles",
+    ))
+}
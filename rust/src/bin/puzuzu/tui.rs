@@ -0,0 +1,542 @@
+use anyhow::{Context, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::{cursor, execute, queue, terminal};
+use puzuzu::{Clue, Direction, Letters, Puzzle, SolutionState, TextGridOptions, Timer};
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// How often the screen is redrawn to tick the timer
+const TICK: Duration = Duration::from_millis(250);
+
+/// Width of a square in the text grid, including its right border
+const SQUARE_COLUMNS: u16 = 5;
+/// Height of a square in the text grid, including its bottom border
+const SQUARE_ROWS: u16 = 3;
+/// The grid is drawn below the title
+const GRID_TOP: u16 = 2;
+
+const HELP: &str =
+    "arrows move · space switches direction · tab next clue · ctrl-k check · ctrl-s save · esc quits";
+
+/// The state of a solve: the puzzle being filled in, the cursor and the
+/// clock
+pub struct Solver {
+    puzzle: Puzzle,
+    cursor: usize,
+    direction: Direction,
+    /// time on the clock when the solve started, from the LTIM extension
+    elapsed_before: Duration,
+    started: Instant,
+    /// time on the clock when the puzzle was solved
+    solved_in: Option<Duration>,
+    /// color wrong squares; never on for locked puzzles
+    check: bool,
+    message: Option<String>,
+}
+
+impl Solver {
+    pub fn new(puzzle: Puzzle) -> Solver {
+        let cursor = (0..puzzle.width() * puzzle.height())
+            .find(|&index| {
+                !puzzle
+                    .grid()
+                    .get_solution_character(index)
+                    .is_some_and(is_black)
+            })
+            .unwrap_or(0);
+        let elapsed_before = puzzle.timer.map(|timer| timer.elapsed).unwrap_or_default();
+        let solved_in = if puzzle.is_fill_correct() {
+            Some(elapsed_before)
+        } else {
+            None
+        };
+
+        Solver {
+            puzzle,
+            cursor,
+            direction: Direction::Across,
+            elapsed_before,
+            started: Instant::now(),
+            solved_in,
+            check: false,
+            message: None,
+        }
+    }
+
+    fn row(&self) -> usize {
+        self.cursor / self.puzzle.width()
+    }
+
+    fn column(&self) -> usize {
+        self.cursor % self.puzzle.width()
+    }
+
+    fn is_black(&self, index: usize) -> bool {
+        self.puzzle
+            .grid()
+            .get_solution_character(index)
+            .is_some_and(is_black)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.solved_in
+            .unwrap_or_else(|| self.elapsed_before + self.started.elapsed())
+    }
+
+    /// Moves to the next white square in the given direction, switching to
+    /// that direction like the TypeScript TUI does
+    pub fn step(&mut self, rows: isize, columns: isize) {
+        self.direction = if rows == 0 {
+            Direction::Across
+        } else {
+            Direction::Down
+        };
+
+        let (mut row, mut column) = (self.row() as isize, self.column() as isize);
+        loop {
+            row += rows;
+            column += columns;
+
+            if row < 0
+                || column < 0
+                || row >= self.puzzle.height() as isize
+                || column >= self.puzzle.width() as isize
+            {
+                return;
+            }
+
+            let index = row as usize * self.puzzle.width() + column as usize;
+            if !self.is_black(index) {
+                self.cursor = index;
+                return;
+            }
+        }
+    }
+
+    fn advance(&mut self) {
+        match self.direction {
+            Direction::Across => self.step(0, 1),
+            Direction::Down => self.step(1, 0),
+        }
+    }
+
+    fn retreat(&mut self) {
+        match self.direction {
+            Direction::Across => self.step(0, -1),
+            Direction::Down => self.step(-1, 0),
+        }
+    }
+
+    pub fn toggle_direction(&mut self) {
+        self.direction = match self.direction {
+            Direction::Across => Direction::Down,
+            Direction::Down => Direction::Across,
+        };
+    }
+
+    pub fn active_clue(&self, direction: Direction) -> Option<&Clue> {
+        match direction {
+            Direction::Across => self.puzzle.get_across_clue(self.row(), self.column()),
+            Direction::Down => self.puzzle.get_down_clue(self.row(), self.column()),
+        }
+    }
+
+    /// The squares of the clue being solved
+    pub fn active_squares(&self) -> Vec<usize> {
+        let clue = match self.active_clue(self.direction) {
+            Some(clue) => clue,
+            None => return vec![self.cursor],
+        };
+
+        (0..clue.length)
+            .map(|offset| match self.direction {
+                Direction::Across => clue.row * self.puzzle.width() + clue.column + offset,
+                Direction::Down => (clue.row + offset) * self.puzzle.width() + clue.column,
+            })
+            .collect()
+    }
+
+    /// Jumps to the start of the next clue in the current direction
+    pub fn next_clue(&mut self) {
+        let clues = match self.direction {
+            Direction::Across => &self.puzzle.clues.across,
+            Direction::Down => &self.puzzle.clues.down,
+        };
+
+        let current = self
+            .active_clue(self.direction)
+            .and_then(|active| clues.iter().position(|clue| clue == active));
+        let next = match current {
+            Some(position) => &clues[(position + 1) % clues.len()],
+            None => match clues.first() {
+                Some(clue) => clue,
+                None => return,
+            },
+        };
+
+        self.cursor = next.row * self.puzzle.width() + next.column;
+    }
+
+    pub fn type_letter(&mut self, letter: char) -> Result<()> {
+        if self.solved_in.is_some() {
+            return Ok(());
+        }
+
+        let entry = letter.to_uppercase().to_string();
+        self.puzzle
+            .set_rebus_fill(self.row(), self.column(), &entry)?;
+        self.advance();
+        self.check_solved();
+
+        Ok(())
+    }
+
+    pub fn delete(&mut self) -> Result<()> {
+        if self.solved_in.is_some() {
+            return Ok(());
+        }
+
+        self.puzzle.set_rebus_fill(self.row(), self.column(), "")?;
+        self.retreat();
+
+        Ok(())
+    }
+
+    fn check_solved(&mut self) {
+        if self.puzzle.is_fill_correct() {
+            self.solved_in = Some(self.elapsed());
+        }
+    }
+
+    pub fn toggle_check(&mut self) {
+        if self.puzzle.solution_state() == SolutionState::Locked {
+            self.message = Some("Locked puzzles can't be checked".to_string());
+        } else {
+            self.check = !self.check;
+        }
+    }
+
+    fn empty_squares(&self) -> usize {
+        let grid = self.puzzle.grid();
+        (0..self.puzzle.width() * self.puzzle.height())
+            .filter(|&index| !self.is_black(index) && grid.get_fill_character(index) == Some('-'))
+            .count()
+    }
+
+    /// Writes the fill and the clock back to the puzzle file
+    pub fn save(&mut self, path: &str) -> Result<()> {
+        self.puzzle.timer = Some(Timer {
+            elapsed: Duration::from_secs(self.elapsed().as_secs()),
+            running: self.solved_in.is_none(),
+        });
+
+        std::fs::write(path, self.puzzle.to_puz()?).context(format!("Failed to write {}", path))
+    }
+}
+
+fn is_black(square: char) -> bool {
+    square == '.' || square == ':'
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        text.chars()
+            .take(width.saturating_sub(1))
+            .chain(std::iter::once('…'))
+            .collect()
+    }
+}
+
+/// Draws a list of clues, scrolled so the active one is visible
+fn draw_clues<W: Write>(
+    out: &mut W,
+    heading: &str,
+    clues: &[Clue],
+    active: Option<&Clue>,
+    focused: bool,
+    (left, top, width, height): (u16, u16, usize, usize),
+) -> Result<()> {
+    queue!(
+        out,
+        cursor::MoveTo(left, top),
+        SetAttribute(Attribute::Bold),
+        Print(heading),
+        SetAttribute(Attribute::Reset)
+    )?;
+
+    let rows = height.saturating_sub(1);
+    let position = active.and_then(|active| clues.iter().position(|clue| clue == active));
+    let first = position.map_or(0, |position| position.saturating_sub(rows / 2));
+
+    for (line, clue) in clues.iter().skip(first).take(rows).enumerate() {
+        let text = truncate(&format!("{:>3} {}", clue.clue_number, clue.text), width);
+        queue!(out, cursor::MoveTo(left, top + 1 + line as u16))?;
+
+        if Some(clue) == active {
+            let attribute = if focused {
+                Attribute::Reverse
+            } else {
+                Attribute::Underlined
+            };
+            queue!(
+                out,
+                SetAttribute(attribute),
+                Print(text),
+                SetAttribute(Attribute::Reset)
+            )?;
+        } else {
+            queue!(out, Print(text))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn draw<W: Write>(out: &mut W, solver: &Solver) -> Result<()> {
+    let (columns, rows) = terminal::size()?;
+    let puzzle = &solver.puzzle;
+
+    queue!(
+        out,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::MoveTo(0, 0),
+        SetAttribute(Attribute::Bold),
+        Print(truncate(&puzzle.title, columns as usize)),
+        SetAttribute(Attribute::Reset)
+    )?;
+    if !puzzle.author.is_empty() {
+        queue!(
+            out,
+            cursor::MoveTo(0, 1),
+            Print(truncate(&puzzle.author, columns as usize))
+        )?;
+    }
+
+    let text = puzzle.to_text(&TextGridOptions {
+        letters: Letters::Fill,
+        colors: true,
+        active: solver.active_squares(),
+        check: solver.check,
+    })?;
+    for (line, text) in text.lines().enumerate() {
+        queue!(out, cursor::MoveTo(0, GRID_TOP + line as u16), Print(text))?;
+    }
+
+    // the clue lists and clock go to the right of the grid
+    let panel_left = puzzle.width() as u16 * SQUARE_COLUMNS + 3;
+    let panel_width = columns.saturating_sub(panel_left) as usize;
+    let status = match solver.solved_in {
+        Some(elapsed) => format!("Solved in {}!", format_duration(elapsed)),
+        None => format!(
+            "{} | {} left",
+            format_duration(solver.elapsed()),
+            solver.empty_squares()
+        ),
+    };
+    queue!(
+        out,
+        cursor::MoveTo(panel_left, 0),
+        Print(truncate(&status, panel_width))
+    )?;
+
+    let list_height = (rows.saturating_sub(GRID_TOP + 3) / 2) as usize;
+    draw_clues(
+        out,
+        "Across",
+        &puzzle.clues.across,
+        solver.active_clue(Direction::Across),
+        solver.direction == Direction::Across,
+        (panel_left, GRID_TOP, panel_width, list_height),
+    )?;
+    draw_clues(
+        out,
+        "Down",
+        &puzzle.clues.down,
+        solver.active_clue(Direction::Down),
+        solver.direction == Direction::Down,
+        (
+            panel_left,
+            GRID_TOP + list_height as u16 + 1,
+            panel_width,
+            list_height,
+        ),
+    )?;
+
+    let active = match solver.active_clue(solver.direction) {
+        Some(clue) => format!("{} {:?}: {}", clue.clue_number, solver.direction, clue.text),
+        None => String::new(),
+    };
+    let footer = solver.message.as_deref().unwrap_or(HELP);
+    queue!(
+        out,
+        cursor::MoveTo(0, rows.saturating_sub(2)),
+        Print(truncate(&active, columns as usize)),
+        cursor::MoveTo(0, rows.saturating_sub(1)),
+        Print(truncate(footer, columns as usize)),
+        // put the terminal's cursor on the letter of the active square
+        cursor::MoveTo(
+            solver.column() as u16 * SQUARE_COLUMNS + 2,
+            GRID_TOP + solver.row() as u16 * SQUARE_ROWS + 2
+        )
+    )?;
+
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Handles a key press, returning false when the solver should quit
+fn handle_key(solver: &mut Solver, key: KeyEvent, path: &str) -> Result<bool> {
+    solver.message = None;
+    let control = key.modifiers.contains(KeyModifiers::CONTROL);
+
+    match key.code {
+        KeyCode::Esc => return Ok(false),
+        KeyCode::Char('c') if control => return Ok(false),
+        KeyCode::Char('s') if control => {
+            solver.message = Some(match solver.save(path) {
+                Ok(()) => format!("Saved {}", path),
+                Err(error) => format!("{:#}", error),
+            });
+        }
+        KeyCode::Char('k') if control => solver.toggle_check(),
+        KeyCode::Left => solver.step(0, -1),
+        KeyCode::Right => solver.step(0, 1),
+        KeyCode::Up => solver.step(-1, 0),
+        KeyCode::Down => solver.step(1, 0),
+        KeyCode::Char(' ') => solver.toggle_direction(),
+        KeyCode::Tab => solver.next_clue(),
+        KeyCode::Backspace => solver.delete()?,
+        KeyCode::Char(letter) if letter.is_alphanumeric() && !control => {
+            solver.type_letter(letter)?
+        }
+        _ => {}
+    }
+
+    Ok(true)
+}
+
+/// Restores the terminal when the solver exits, even on errors
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        execute!(std::io::stdout(), terminal::EnterAlternateScreen)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(std::io::stdout(), terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+pub fn run(puzzle: Puzzle, path: &str) -> Result<()> {
+    let mut solver = Solver::new(puzzle);
+    let _guard = TerminalGuard::enter()?;
+    let mut out = std::io::stdout();
+
+    loop {
+        draw(&mut out, &solver)?;
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !handle_key(&mut solver, key, path)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Solver;
+    use anyhow::Result;
+    use puzuzu::{Direction, Puzzle};
+
+    fn mini() -> Result<Solver> {
+        Ok(Solver::new(Puzzle::from_xd(
+            "\n\nCAT\nAGO\nTE#\n\n\nA1. Feline\nA4. Again\nA5. Tee\n\nD1. Carts\nD2. Year\nD3. To\n",
+        )?))
+    }
+
+    #[test]
+    fn test_movement() -> Result<()> {
+        let mut solver = mini()?;
+        assert_eq!(solver.cursor, 0);
+
+        solver.step(1, 0);
+        assert_eq!(solver.direction, Direction::Down);
+        assert_eq!(solver.active_clue(Direction::Down).unwrap().clue_number, 1);
+        assert_eq!(solver.active_squares(), vec![0, 3, 6]);
+
+        // black squares are skipped, and the cursor stops at the edge
+        solver.step(0, 1);
+        solver.step(0, 1);
+        assert_eq!(solver.cursor, 5);
+        solver.step(1, 0);
+        assert_eq!(solver.cursor, 5);
+
+        solver.toggle_direction();
+        solver.next_clue();
+        assert_eq!(solver.cursor, 6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_solving() -> Result<()> {
+        let mut solver = mini()?;
+
+        for letter in "cat".chars() {
+            solver.type_letter(letter)?;
+        }
+        assert_eq!(solver.puzzle.fill, "CAT-----.");
+
+        solver.delete()?;
+        assert_eq!(solver.cursor, 1);
+        assert_eq!(solver.puzzle.fill, "CA------.");
+
+        solver.cursor = 2;
+        solver.type_letter('T')?;
+        solver.cursor = 3;
+        for letter in "AGO".chars() {
+            solver.type_letter(letter)?;
+        }
+        solver.cursor = 6;
+        solver.type_letter('T')?;
+        assert!(solver.solved_in.is_none());
+        solver.type_letter('E')?;
+        assert!(solver.solved_in.is_some());
+
+        // the clock stops and the grid is frozen once solved
+        solver.cursor = 0;
+        solver.type_letter('X')?;
+        assert_eq!(solver.puzzle.fill, "CATAGOTE.");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use puzuzu::{Diagnostic, Puzzle, Severity};
+use serde_json::json;
+use std::fmt::Write;
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+/// Lists the diagnostics of a file for people, under a count of each
+/// severity
+fn describe(path: &str, diagnostics: &[Diagnostic]) -> String {
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity() == severity)
+            .count()
+    };
+
+    let mut text = String::new();
+    // writing to a String can't fail
+    let _ = writeln!(
+        text,
+        "{}: {} errors, {} warnings",
+        path,
+        count(Severity::Error),
+        count(Severity::Warning)
+    );
+    for diagnostic in diagnostics {
+        let _ = writeln!(
+            text,
+            "  {:<9}{}",
+            severity_name(diagnostic.severity()),
+            diagnostic
+        );
+    }
+
+    text
+}
+
+/// A diagnostic for scripts: its kind and fields, along with its severity
+/// and message
+fn diagnostic_json(diagnostic: &Diagnostic) -> Result<serde_json::Value> {
+    let mut value = serde_json::to_value(diagnostic).context("Failed to serialize diagnostic")?;
+    if let Some(object) = value.as_object_mut() {
+        object.insert(
+            "severity".to_string(),
+            json!(severity_name(diagnostic.severity())),
+        );
+        object.insert("message".to_string(), json!(diagnostic.to_string()));
+    }
+
+    Ok(value)
+}
+
+fn validate_file(path: &str) -> Result<Vec<Diagnostic>> {
+    let data = std::fs::read(path).context(format!("Failed to read {}", path))?;
+    let puzzle = Puzzle::from_puz_unverified(data).context(format!("Failed to parse {}", path))?;
+
+    puzzle
+        .validate()
+        .context(format!("Failed to validate {}", path))
+}
+
+/// `puzuzu validate [--json] paths...`: lists every problem with each file
+/// and returns whether none of them had errors. Warnings alone don't fail.
+pub fn run(args: &[String]) -> Result<bool> {
+    let json = args.iter().any(|arg| arg == "--json");
+    let paths = args
+        .iter()
+        .filter(|arg| arg.as_str() != "--json")
+        .collect::<Vec<_>>();
+    if paths.is_empty() || paths.iter().any(|path| path.starts_with('-')) {
+        crate::usage();
+    }
+
+    let mut valid = true;
+    let mut reports = vec![];
+    for path in paths {
+        match validate_file(path) {
+            Ok(diagnostics) => {
+                valid &= diagnostics
+                    .iter()
+                    .all(|diagnostic| diagnostic.severity() != Severity::Error);
+
+                if json {
+                    reports.push(json!({
+                        "file": path,
+                        "diagnostics": diagnostics
+                            .iter()
+                            .map(diagnostic_json)
+                            .collect::<Result<Vec<_>>>()?,
+                    }));
+                } else {
+                    print!("{}", describe(path, &diagnostics));
+                }
+            }
+            Err(error) => {
+                valid = false;
+
+                if json {
+                    reports.push(json!({ "file": path, "error": format!("{:#}", error) }));
+                } else {
+                    println!("{}: {:#}", path, error);
+                }
+            }
+        }
+    }
+
+    if json {
+        println!("{:#}", serde_json::Value::Array(reports));
+    }
+
+    Ok(valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe, diagnostic_json};
+    use anyhow::Result;
+    use puzuzu::{Diagnostic, Direction};
+
+    #[test]
+    fn test_describe() -> Result<()> {
+        let diagnostics = vec![
+            Diagnostic::ClueCountMismatch {
+                clues: 77,
+                slots: 78,
+            },
+            Diagnostic::TwoLetterWord {
+                direction: Direction::Down,
+                number: 3,
+                row: 0,
+                column: 2,
+            },
+        ];
+
+        assert_eq!(
+            describe("puzzle.puz", &diagnostics),
+            "puzzle.puz: 1 errors, 1 warnings
+  error    The puzzle has 77 clues but its grid has 78 words
+  warning  3 Down is two letters long
+"
+        );
+
+        let json = diagnostic_json(&diagnostics[1])?;
+        assert_eq!(json["kind"], "twoLetterWord");
+        assert_eq!(json["direction"], "Down");
+        assert_eq!(json["severity"], "warning");
+        assert_eq!(json["message"], "3 Down is two letters long");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use crate::Puzzle;
+use anyhow::{Context, Result};
+
+/// A checksum stored in a .puz file alongside the value calculated from
+/// the data it covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    /// "global", "header", "magic", or the code of the extension, such as
+    /// "GEXT"
+    pub name: String,
+    pub stored: u64,
+    pub calculated: u64,
+}
+
+impl Checksum {
+    pub fn is_valid(&self) -> bool {
+        self.stored == self.calculated
+    }
+}
+
+impl Puzzle {
+    /// Compares every checksum stored in the file with one calculated from
+    /// the puzzle: the global, header and magic checksums followed by one
+    /// for each extension, in file order
+    pub fn checksums(&self) -> Result<Vec<Checksum>> {
+        let mut checksums = vec![
+            Checksum {
+                name: "global".to_string(),
+                stored: self.header.global_checksum as u64,
+                calculated: self
+                    .calculate_global_checksum()
+                    .context("Failed to calculate global checksum")?
+                    as u64,
+            },
+            Checksum {
+                name: "header".to_string(),
+                stored: self.header.header_checksum as u64,
+                calculated: self
+                    .header
+                    .calculate_checksum()
+                    .context("Failed to calculate header checksum")?
+                    as u64,
+            },
+            Checksum {
+                name: "magic".to_string(),
+                stored: self.header.magic_checksum,
+                calculated: self
+                    .calculate_magic_checksum()
+                    .context("Failed to calculate magic checksum")?,
+            },
+        ];
+
+        checksums.extend(self.extensions.iter().map(|extension| Checksum {
+            name: String::from_utf8_lossy(&extension.code).into_owned(),
+            stored: extension.stored_checksum() as u64,
+            calculated: extension.calculated_checksum() as u64,
+        }));
+
+        Ok(checksums)
+    }
+
+    /// Recalculates every stored checksum, so the puzzle is written with
+    /// correct ones even if it was parsed with `repair_checksums` turned
+    /// off. Returns the checksums that changed, with `stored` holding the
+    /// old value and `calculated` the new one.
+    pub fn repair_checksums(&mut self) -> Result<Vec<Checksum>> {
+        let changed = self
+            .checksums()?
+            .into_iter()
+            .filter(|checksum| !checksum.is_valid())
+            .collect();
+
+        self.refresh_checksums()?;
+        for extension in &mut self.extensions {
+            extension.checksum = extension.calculated_checksum();
+        }
+
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Checksum;
+    use crate::{ChecksumVerification, ParseOptions, Puzzle};
+    use anyhow::Result;
+
+    #[test]
+    fn test_checksums() -> Result<()> {
+        let data = std::fs::read("../test_files/nyt_rebus_with_notes_and_shape.puz")?;
+        let puzzle = Puzzle::from_puz(data.clone())?;
+
+        let checksums = puzzle.checksums()?;
+        let names = checksums
+            .iter()
+            .map(|checksum| checksum.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["global", "header", "magic", "GRBS", "RTBL", "LTIM", "GEXT"]
+        );
+        assert!(checksums.iter().all(|checksum| checksum.is_valid()));
+
+        // flip a bit of the first letter of the solution, just after the
+        // 52 byte header
+        let mut damaged = data;
+        damaged[52] ^= 1;
+        assert!(Puzzle::from_puz(damaged.clone()).is_err());
+
+        let puzzle = Puzzle::from_puz_unverified(damaged)?;
+        let invalid = puzzle
+            .checksums()?
+            .into_iter()
+            .filter(|checksum| !checksum.is_valid())
+            .map(|checksum| checksum.name)
+            .collect::<Vec<_>>();
+        assert_eq!(invalid, ["global", "magic"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_checksums() -> Result<()> {
+        let data = std::fs::read("../test_files/nyt_rebus_with_notes_and_shape.puz")?;
+        // change the first letter of the solution from G to F
+        let mut damaged = data.clone();
+        damaged[52] ^= 1;
+        // the checksum follows the extension's code and length
+        let gext = damaged
+            .windows(4)
+            .position(|window| window == b"GEXT")
+            .unwrap();
+        damaged[gext + 6] ^= 1;
+
+        let options = ParseOptions {
+            verify_checksums: ChecksumVerification::Ignore,
+            repair_checksums: false,
+        };
+        let mut puzzle = Puzzle::from_puz_with_options(damaged, &options)?;
+        let changed = puzzle.repair_checksums()?;
+        let names = changed
+            .iter()
+            .map(|checksum| checksum.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["global", "magic", "GEXT"]);
+        assert!(changed.iter().all(|checksum| !checksum.is_valid()));
+
+        assert!(puzzle.checksums()?.iter().all(Checksum::is_valid));
+        assert!(puzzle.repair_checksums()?.is_empty());
+
+        // the same as making the change to the solution with this library
+        let mut edited = Puzzle::from_puz(data)?;
+        edited.solution.replace_range(..1, "F");
+        assert!(puzzle.to_puz()? == edited.to_puz()?);
+
+        Ok(())
+    }
+}
@@ -13,6 +13,12 @@ pub struct Clue {
     pub length: usize,
 }
 
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy, Serialize)]
+pub enum Direction {
+    Across,
+    Down,
+}
+
 #[derive(Serialize)]
 pub struct Clues {
     pub across: Vec<Clue>,
@@ -43,7 +49,7 @@ impl Clues {
 
             if is_across && len_across > 1 {
                 across.push(Clue {
-                    clue_number: clue_number,
+                    clue_number,
                     text: clue_iter
                         .next()
                         .ok_or(Error::msg("Ran out of provided clues"))?
@@ -62,7 +68,7 @@ impl Clues {
 
             if is_down && len_down > 1 {
                 down.push(Clue {
-                    clue_number: clue_number,
+                    clue_number,
                     text: clue_iter
                         .next()
                         .ok_or(Error::msg("Ran out of provided clues"))?
@@ -82,4 +88,27 @@ impl Clues {
 
         Ok(Self { across, down })
     }
+
+    /// Numbers the grid without any clue text, which is useful for matching
+    /// clues from other formats up with the squares they belong to
+    pub fn numbering(grid: Grid) -> Clues {
+        let empty = String::new();
+        Self::new(grid, std::iter::repeat(&empty))
+            .expect("an endless supply of clues cannot run out")
+    }
+
+    /// Iterates over the clues in the order they're stored in .puz files: by
+    /// number, with across before down
+    pub fn in_puz_order(&self) -> impl Iterator<Item = (Direction, &Clue)> {
+        let mut clues = self
+            .across
+            .iter()
+            .map(|clue| (Direction::Across, clue))
+            .chain(self.down.iter().map(|clue| (Direction::Down, clue)))
+            .collect::<Vec<_>>();
+
+        clues.sort_by_key(|(direction, clue)| (clue.clue_number, *direction == Direction::Down));
+
+        clues.into_iter()
+    }
 }
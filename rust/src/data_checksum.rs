@@ -4,9 +4,9 @@ pub(crate) fn data_checksum(data: &[u8], initial_checksum: u16) -> u16 {
         .fold(initial_checksum, |mut checksum, &byte| -> u16 {
             // right-shift one with wrap-around
             let low_bit = checksum & 0x0001;
-            checksum = checksum >> 1;
+            checksum >>= 1;
             if low_bit > 0 {
-                checksum = checksum | 0x8000;
+                checksum |= 0x8000;
             }
 
             // then add in the data and clear any carried bit past 16
@@ -0,0 +1,223 @@
+use crate::validate::Diagnostic;
+use thiserror::Error;
+use wasm_bindgen::prelude::*;
+
+/// Everything that can go wrong reading a .puz file. Offsets are in bytes
+/// from the start of the data, including anything before the puzzle itself.
+#[derive(Error, Debug)]
+pub enum PuzError {
+    /// the data isn't a puz file, or the puzzle in it couldn't be found
+    #[error("Cannot find '{0}' in data")]
+    SeekError(String),
+    #[error("Encoding has not been inferred")]
+    EncodingNotInferred,
+    /// the data ends in the middle of a field
+    #[error("Data ends at byte {offset} in the middle of the {field}")]
+    Truncated { field: String, offset: usize },
+    #[error("Invalid {field} at byte {offset}: {message}")]
+    InvalidField {
+        field: String,
+        offset: usize,
+        message: String,
+    },
+    #[error("Failed to decode the {field} at byte {offset}: {message}")]
+    Decode {
+        field: String,
+        offset: usize,
+        message: String,
+    },
+    #[error("Failed to parse the {code} extension: {message}")]
+    InvalidExtension { code: String, message: String },
+    /// the file was read, but it's damaged or inconsistent, such as having
+    /// a checksum that doesn't match
+    #[error("{0}")]
+    Invalid(Diagnostic),
+    /// errors from the rest of the library, such as text that can't be
+    /// encoded to calculate a checksum
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+/// Keeps the details of a `PuzError` that was passed up as an
+/// `anyhow::Error`, such as from a writer checking its header
+impl From<anyhow::Error> for PuzError {
+    fn from(error: anyhow::Error) -> PuzError {
+        error.downcast().unwrap_or_else(PuzError::Other)
+    }
+}
+
+impl PuzError {
+    /// The name of the variant, for telling errors apart in JS
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PuzError::SeekError(_) => "seekError",
+            PuzError::EncodingNotInferred => "encodingNotInferred",
+            PuzError::Truncated { .. } => "truncated",
+            PuzError::InvalidField { .. } => "invalidField",
+            PuzError::Decode { .. } => "decode",
+            PuzError::InvalidExtension { .. } => "invalidExtension",
+            PuzError::Invalid(_) => "invalid",
+            PuzError::Other(_) => "other",
+        }
+    }
+
+    /// The field being read when the error happened
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            PuzError::Truncated { field, .. }
+            | PuzError::InvalidField { field, .. }
+            | PuzError::Decode { field, .. } => Some(field),
+            PuzError::InvalidExtension { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+
+    /// Where in the data the error happened
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            PuzError::Truncated { offset, .. }
+            | PuzError::InvalidField { offset, .. }
+            | PuzError::Decode { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+}
+
+/// Converts to a JS `Error` named `PuzError` with `kind`, and `field` and
+/// `offset` where they're known, as properties
+impl From<PuzError> for JsValue {
+    fn from(error: PuzError) -> JsValue {
+        let js_error = js_sys::Error::new(&error.to_string());
+        js_error.set_name("PuzError");
+
+        let set = |key: &str, value: JsValue| {
+            // setting a property on a new Error can't fail
+            let _ = js_sys::Reflect::set(&js_error, &JsValue::from_str(key), &value);
+        };
+        set("kind", JsValue::from_str(error.kind()));
+        if let Some(field) = error.field() {
+            set("field", JsValue::from_str(field));
+        }
+        if let Some(offset) = error.offset() {
+            set("offset", JsValue::from_f64(offset as f64));
+        }
+
+        js_error.into()
+    }
+}
+
+/// Converts an error from any part of the library for the wasm bindings,
+/// so JS always gets a `PuzError`
+pub(crate) fn js_error(error: impl Into<PuzError>) -> JsValue {
+    error.into().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PuzError;
+    use crate::{Diagnostic, Extension, Header, Puzzle};
+    use anyhow::Result;
+    use std::io::Cursor;
+
+    fn fixture() -> Result<Vec<u8>> {
+        Ok(std::fs::read(
+            "../test_files/nyt_rebus_with_notes_and_shape.puz",
+        )?)
+    }
+
+    #[test]
+    fn test_not_a_puz_file() {
+        let error = Puzzle::from_puz(b"<ACROSS PUZZLE>".to_vec()).err().unwrap();
+        assert!(matches!(error, PuzError::SeekError(_)));
+        assert_eq!(error.kind(), "seekError");
+    }
+
+    #[test]
+    fn test_from_anyhow() {
+        let error = anyhow::Error::from(PuzError::EncodingNotInferred).context("Reading");
+        assert_eq!(PuzError::from(error).kind(), "encodingNotInferred");
+
+        let error = PuzError::from(anyhow::Error::msg("Something else"));
+        assert_eq!(error.kind(), "other");
+        assert_eq!(error.to_string(), "Something else");
+    }
+
+    #[test]
+    fn test_no_room_before_magic() {
+        for data in [&b"ACROSS&DOWN"[..], b"xACROSS&DOWN"] {
+            let error = Puzzle::from_puz_unverified(data.to_vec()).err().unwrap();
+            assert_eq!(error.kind(), "invalidField");
+            assert_eq!(error.field(), Some("ACROSS&DOWN"));
+            assert_eq!(error.offset(), Some(data.len() - 11));
+        }
+
+        // with room for the checksum, the header is cut off instead
+        let error = Puzzle::from_puz_unverified(b"xxACROSS&DOWN".to_vec())
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), "truncated");
+    }
+
+    #[test]
+    fn test_truncated() -> Result<()> {
+        let data = fixture()?;
+
+        // 52 byte header, then two 15x15 grids and part of the title
+        let end = 52 + 225 + 225 + 3;
+        let error = Puzzle::from_puz(data[..end].to_vec()).err().unwrap();
+        assert_eq!(error.field(), Some("title"));
+        assert_eq!(error.offset(), Some(end));
+
+        let error = Header::from_cursor(&mut Cursor::new(&data[..20])).unwrap_err();
+        assert_eq!(error.kind(), "truncated");
+        assert_eq!(error.field(), Some("magic checksum"));
+        assert_eq!(error.offset(), Some(16));
+
+        // GRBS is the first extension; cut it off after its header
+        let grbs = data
+            .windows(4)
+            .position(|window| window == b"GRBS")
+            .unwrap();
+        let error =
+            Extension::parse_extensions_from_cursor(&mut Cursor::new(&data[grbs..grbs + 20]))
+                .unwrap_err();
+        assert_eq!(error.field(), Some("GRBS extension"));
+        assert_eq!(error.offset(), Some(8));
+
+        // the clues and notes come just before the extensions; cut the last
+        // clue in half
+        let puzzle = Puzzle::from_puz(data.clone())?;
+        let notes = puzzle.notes.len() + 1;
+        let last_clue = puzzle.all_clues.last().unwrap().len() + 1;
+        let error = Puzzle::from_puz(data[..grbs - notes - last_clue / 2].to_vec())
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.field(),
+            Some(format!("clue #{}", puzzle.all_clues.len()).as_str())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid() -> Result<()> {
+        let mut data = fixture()?;
+        // the puzzle type follows the width, height and clue count
+        data[0x30] = 0xff;
+        let error = Puzzle::from_puz(data).err().unwrap();
+        assert_eq!(error.field(), Some("puzzle type"));
+        assert_eq!(error.offset(), Some(0x30));
+
+        let mut data = fixture()?;
+        data[52] ^= 1;
+        let error = Puzzle::from_puz(data).err().unwrap();
+        assert!(matches!(
+            error,
+            PuzError::Invalid(Diagnostic::ChecksumMismatch { ref name, .. }) if name == "global"
+        ));
+        assert_eq!(error.offset(), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use crate::clues::{Clue, Direction};
+use crate::square::Square;
+use crate::Puzzle;
+use anyhow::{Error, Result};
+
+/// Across Lite copyrights usually start with ©, which Exolve adds itself
+fn strip_copyright_symbol(copyright: &str) -> &str {
+    copyright
+        .trim_start_matches('©')
+        .trim_start_matches("(c)")
+        .trim_start_matches("(C)")
+        .trim_start()
+}
+
+impl Puzzle {
+    /// Writes the puzzle as an Exolve block (exolve-begin ... exolve-end),
+    /// ready to be pasted into an Exolve HTML page or passed to
+    /// `createExolve`. Enumerations are the number of letters in each answer.
+    pub fn to_exolve(&self) -> Result<String> {
+        self.ensure_unlocked("they can be written for Exolve")?;
+
+        let grid = self.grid();
+        let mut exolve = String::from("exolve-begin\n");
+        exolve.push_str(&format!("  exolve-width: {}\n", self.width()));
+        exolve.push_str(&format!("  exolve-height: {}\n", self.height()));
+
+        for (name, value) in [
+            ("title", self.title.as_str()),
+            ("setter", self.author.as_str()),
+            ("copyright", strip_copyright_symbol(&self.copyright)),
+        ] {
+            if !value.is_empty() {
+                exolve.push_str(&format!("  exolve-{}: {}\n", name, value));
+            }
+        }
+
+        if !self.notes.is_empty() {
+            exolve.push_str("  exolve-preamble:\n");
+            for line in self.notes.lines() {
+                exolve.push_str(&format!("    {}\n", line));
+            }
+        }
+
+        if !self.rebus.is_empty() {
+            exolve.push_str("  exolve-option: rebus-cells\n");
+        }
+
+        exolve.push_str("  exolve-grid:\n");
+        for row in 0..self.height() {
+            let mut squares = vec![];
+            for column in 0..self.width() {
+                let index = row * self.width() + column;
+                let square = grid
+                    .get_solution(index)
+                    .ok_or_else(|| Error::msg(format!("Square {} has no solution", index)))?;
+
+                if square.chars().all(Square::is_black_square) {
+                    squares.push(".".to_string());
+                } else if grid.get_flags(index).is_circled() {
+                    squares.push(format!("{}@", square));
+                } else {
+                    squares.push(square);
+                }
+            }
+
+            // rebus cells have to be separated, and spacing is allowed otherwise
+            exolve.push_str(&format!("    {}\n", squares.join(" ")));
+        }
+
+        let directions: [(&str, Direction, &[Clue]); 2] = [
+            ("across", Direction::Across, &self.clues.across),
+            ("down", Direction::Down, &self.clues.down),
+        ];
+        for (name, direction, clues) in directions {
+            exolve.push_str(&format!("  exolve-{}:\n", name));
+
+            for clue in clues {
+                let letters = grid.get_answer(clue, direction).chars().count();
+                exolve.push_str(&format!(
+                    "    {} {} ({})\n",
+                    clue.clue_number, clue.text, letters
+                ));
+            }
+        }
+
+        exolve.push_str("exolve-end\n");
+
+        Ok(exolve)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::read_fixture;
+    use anyhow::Result;
+
+    #[test]
+    fn test_to_exolve() -> Result<()> {
+        let mut puzzle = read_fixture("zack.puz")?;
+        puzzle.title = "Zack".to_string();
+        puzzle.author = "Someone".to_string();
+        // Exolve adds its own copyright symbol
+        puzzle.copyright = "© 2021 Someone".to_string();
+        puzzle.notes = "A tiny puzzle".to_string();
+        puzzle.rebus.set(5, "RU")?;
+        let mut flags = puzzle.grid().get_flags(0);
+        flags.set_circled(true);
+        puzzle.markup.set(0, flags)?;
+
+        assert_eq!(
+            puzzle.to_exolve()?,
+            r#"exolve-begin
+  exolve-width: 5
+  exolve-height: 5
+  exolve-title: Zack
+  exolve-setter: Someone
+  exolve-copyright: 2021 Someone
+  exolve-preamble:
+    A tiny puzzle
+  exolve-option: rebus-cells
+  exolve-grid:
+    X@ K C D .
+    RU U R A L
+    A M A L E
+    Y A T E S
+    . R E K T
+  exolve-across:
+    1 Webcomic with a recurring "My Hobby" motif (4)
+    5 Counterpart of urban in an oft-cited political divide (6)
+    7 Pre-noon alcoholic beverage featured in an SNL commerical parody (5)
+    8 Sally who served as acting United States Attorney General for ten days in January 2017 (5)
+    9 Fallen in Fortnite, say (4)
+  exolve-down:
+    1 Bare bones visualization? (5)
+    2 Harold's travel partner in a 2004 stoner comedy (5)
+    3 Rust package component (5)
+    4 Any "Exterminate!"-shouting nemesis, in Doctor Who (5)
+    6 In case (4)
+exolve-end
+"#
+        );
+
+        Ok(())
+    }
+}
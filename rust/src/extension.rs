@@ -1,5 +1,14 @@
 use crate::data_checksum::data_checksum;
+use crate::error::PuzError;
+use crate::puzzle_buffer::{read_bytes, read_u16};
 use anyhow::{Context, Error, Result};
+use std::convert::TryFrom;
+
+/// Extensions this library understands; others are kept but not parsed
+pub(crate) const KNOWN_EXTENSIONS: [&str; 5] = ["GRBS", "RTBL", "GEXT", "RUSR", "LTIM"];
+
+/// Bytes in the code, length and checksum before an extension's data
+const HEADER_SIZE: usize = 8;
 
 struct ExtensionHeader {
     pub code: [u8; 4],
@@ -10,25 +19,16 @@ struct ExtensionHeader {
 impl ExtensionHeader {
     fn parse_from_cursor<T: AsRef<[u8]>>(
         reader: &mut std::io::Cursor<T>,
-    ) -> Result<ExtensionHeader> {
-        use byteorder::{LittleEndian, ReadBytesExt};
-        use std::io::Read;
-
+    ) -> Result<ExtensionHeader, PuzError> {
         // 4s
         let mut code = [0u8; 4];
-        reader
-            .read_exact(&mut code)
-            .map_err(|_e| Error::msg("Failed to parse extension code"))?;
+        code.copy_from_slice(&read_bytes(reader, 4, "extension code")?);
 
         // H
-        let length = reader
-            .read_u16::<LittleEndian>()
-            .map_err(|_e| Error::msg("Failed to parse extension length"))?;
+        let length = read_u16(reader, "extension length")?;
 
         // H
-        let checksum = reader
-            .read_u16::<LittleEndian>()
-            .map_err(|_e| Error::msg("Failed to parse extension length"))?;
+        let checksum = read_u16(reader, "extension checksum")?;
 
         Ok(ExtensionHeader {
             code,
@@ -38,51 +38,143 @@ impl ExtensionHeader {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Extension {
     pub code: [u8; 4],
     pub bytes: Vec<u8>,
+    /// the checksum stored in the extension's header, which may not match
+    /// the bytes if the file is damaged
+    pub(crate) checksum: u16,
 }
 
 impl Extension {
+    pub fn new(code: [u8; 4], bytes: Vec<u8>) -> Extension {
+        let checksum = data_checksum(&bytes, 0);
+        Extension {
+            code,
+            bytes,
+            checksum,
+        }
+    }
+
+    /// The checksum stored in the extension's header
+    pub fn stored_checksum(&self) -> u16 {
+        self.checksum
+    }
+
+    /// The checksum of the extension's bytes
+    pub fn calculated_checksum(&self) -> u16 {
+        data_checksum(&self.bytes, 0)
+    }
+
+    /// Finds the extension with the given code
+    pub fn find<'a>(extensions: &'a [Extension], code: &[u8; 4]) -> Option<&'a Extension> {
+        extensions.iter().find(|extension| &extension.code == code)
+    }
+
+    /// Replaces the bytes of the extension with the given code, keeping its
+    /// position so files round-trip. If there is no such extension, one is
+    /// added only when `add_if_missing` is set.
+    pub fn replace(
+        extensions: &mut Vec<Extension>,
+        code: [u8; 4],
+        bytes: Vec<u8>,
+        add_if_missing: bool,
+    ) {
+        match extensions
+            .iter_mut()
+            .find(|extension| extension.code == code)
+        {
+            // unchanged extensions keep the checksum they were read with
+            Some(extension) if extension.bytes == bytes => {}
+            Some(extension) => *extension = Extension::new(code, bytes),
+            None if add_if_missing => extensions.push(Extension::new(code, bytes)),
+            None => {}
+        }
+    }
+
+    /// Parses extensions until the data runs out. Checksums are kept but not
+    /// verified; see `Puzzle::checksums`. Bytes after the extensions that
+    /// can't be one, such as a line break, are left for the caller, but a
+    /// known extension that's cut off is an error.
     pub fn parse_extensions_from_cursor<T: AsRef<[u8]>>(
         reader: &mut std::io::Cursor<T>,
-    ) -> Result<Vec<Extension>> {
-        use byteorder::ReadBytesExt;
-        use std::io::Read;
+    ) -> Result<Vec<Extension>, PuzError> {
         let mut extensions = vec![];
 
-        while let Ok(header) = ExtensionHeader::parse_from_cursor(reader) {
+        loop {
+            let start = reader.position();
+            let remaining = reader
+                .get_ref()
+                .as_ref()
+                .len()
+                .saturating_sub(start as usize);
+            if remaining < HEADER_SIZE {
+                break;
+            }
+
+            let header = ExtensionHeader::parse_from_cursor(reader)?;
+            let code = String::from_utf8_lossy(&header.code);
+            if remaining < HEADER_SIZE + header.length as usize + 1
+                && !KNOWN_EXTENSIONS.contains(&code.as_ref())
+            {
+                reader.set_position(start);
+                break;
+            }
+
+            let field = format!("{} extension", code);
+
             // extension data is represented as a null-terminated string,
             // but since the data can contain nulls we can't use read_string
-            let mut extension_bytes = vec![0u8; header.length as usize];
-            reader.read_exact(&mut extension_bytes).context(format!(
-                "Failed to read {} bytes from extension",
-                header.length
-            ))?;
-
-            reader
-                .read_u8()
-                .context("Failed to see trailing byte after extension")?;
-            let calculated_checksum = data_checksum(&extension_bytes, 0);
-            if calculated_checksum != header.checksum {
-                return Err(Error::msg(format!(
-                    "Extension {} calculated checksum ({}) does not match header ({})",
-                    std::str::from_utf8(&header.code).context(format!(
-                        "Failed to stringify extension code {:?}",
-                        header.code
-                    ))?,
-                    calculated_checksum,
-                    header.checksum
-                )));
-            }
+            let bytes = read_bytes(reader, header.length as usize, &field)?;
+            read_bytes(reader, 1, &format!("trailing byte of the {}", field))?;
 
             extensions.push(Extension {
                 code: header.code,
-                bytes: extension_bytes,
+                bytes,
+                checksum: header.checksum,
             });
         }
 
         Ok(extensions)
     }
+
+    /// Packs the extension with its header and stored checksum
+    pub fn pack(&self) -> Result<Vec<u8>> {
+        use byteorder::{LittleEndian, WriteBytesExt};
+        use std::io::Write;
+        let mut buffer = vec![];
+
+        let length = u16::try_from(self.bytes.len()).map_err(|_e| {
+            Error::msg(format!(
+                "Extension is {} bytes long, which is too long to pack",
+                self.bytes.len()
+            ))
+        })?;
+
+        // 4s
+        buffer
+            .write_all(&self.code)
+            .context("Failed to pack extension code")?;
+
+        // H
+        buffer
+            .write_u16::<LittleEndian>(length)
+            .context("Failed to pack extension length")?;
+
+        // H
+        buffer
+            .write_u16::<LittleEndian>(self.checksum)
+            .context("Failed to pack extension checksum")?;
+
+        buffer
+            .write_all(&self.bytes)
+            .context("Failed to pack extension data")?;
+
+        buffer
+            .write_u8(0)
+            .context("Failed to pack trailing byte after extension")?;
+
+        Ok(buffer)
+    }
 }
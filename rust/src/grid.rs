@@ -1,3 +1,6 @@
+use crate::clues::{Clue, Direction};
+use crate::markup::{Markup, SquareFlags};
+use crate::rebus::{Rebus, RebusFill};
 use crate::square::Square;
 use crate::Puzzle;
 use serde::ser::{SerializeSeq, Serializer};
@@ -6,7 +9,9 @@ use serde::Serialize;
 #[derive(Serialize)]
 pub struct Cell {
     black: bool,
-    solution: char,
+    /// the full solution, which may be several characters for rebus squares
+    solution: String,
+    flags: SquareFlags,
 }
 
 pub struct Grid<'a> {
@@ -14,6 +19,9 @@ pub struct Grid<'a> {
     pub solution: &'a str,
     pub width: usize,
     pub height: usize,
+    pub rebus: Option<&'a Rebus>,
+    pub markup: Option<&'a Markup>,
+    pub rebus_fill: Option<&'a RebusFill>,
 }
 
 impl<'a> Serialize for Grid<'a> {
@@ -28,12 +36,13 @@ impl<'a> Serialize for Grid<'a> {
                     .expect("Tried to serialize invalid grid (no fill in place)");
 
                 let solution = self
-                    .get_solution_character(cell_index)
+                    .get_solution(cell_index)
                     .expect("Tried to serialize invalid grid (no solution in place)");
 
                 row.push(Cell {
                     black: Square::is_black_square(square),
                     solution,
+                    flags: self.get_flags(cell_index),
                 });
             }
 
@@ -51,6 +60,9 @@ impl<'a> Grid<'a> {
             solution,
             width,
             height,
+            rebus: None,
+            markup: None,
+            rebus_fill: None,
         }
     }
 
@@ -60,6 +72,9 @@ impl<'a> Grid<'a> {
             solution: &puzzle.solution,
             width: puzzle.header.width,
             height: puzzle.header.height,
+            rebus: Some(&puzzle.rebus),
+            markup: Some(&puzzle.markup),
+            rebus_fill: Some(&puzzle.rebus_fill),
         }
     }
 
@@ -67,10 +82,61 @@ impl<'a> Grid<'a> {
         self.fill.chars().nth(index)
     }
 
+    /// Returns the solver's full entry in a square, which is their rebus
+    /// entry if there is one and the fill character otherwise
+    pub fn get_fill(&self, index: usize) -> Option<String> {
+        match self.rebus_fill.and_then(|rebus_fill| rebus_fill.get(index)) {
+            Some(entry) => Some(entry.to_string()),
+            None => self.get_fill_character(index).map(String::from),
+        }
+    }
+
     pub fn get_solution_character(&self, index: usize) -> Option<char> {
         self.solution.chars().nth(index)
     }
 
+    /// Returns the full solution of a square, which is the rebus solution
+    /// if there is one and the solution character otherwise
+    pub fn get_solution(&self, index: usize) -> Option<String> {
+        match self.rebus.and_then(|rebus| rebus.get(index)) {
+            Some(rebus) => Some(rebus.to_string()),
+            None => self.get_solution_character(index).map(String::from),
+        }
+    }
+
+    /// Returns the full answer to a clue, with rebus squares spelled out
+    pub fn get_answer(&self, clue: &Clue, direction: Direction) -> String {
+        (0..clue.length)
+            .filter_map(|offset| {
+                let (row, column) = match direction {
+                    Direction::Across => (clue.row, clue.column + offset),
+                    Direction::Down => (clue.row + offset, clue.column),
+                };
+
+                self.get_solution(row * self.width + column)
+            })
+            .collect()
+    }
+
+    /// Returns the flags (circled, revealed etc.) of a square
+    pub fn get_flags(&self, index: usize) -> SquareFlags {
+        self.markup
+            .map(|markup| markup.get(index))
+            .unwrap_or_default()
+    }
+
+    /// Checks an answer for a square against its solution. Rebus squares
+    /// accept either the full solution or its first character, like
+    /// Across Lite does.
+    pub fn check_answer(&self, index: usize, answer: &str) -> bool {
+        match self.rebus.and_then(|rebus| rebus.get(index)) {
+            Some(rebus) => answer == rebus || answer.chars().eq(rebus.chars().take(1)),
+            None => self
+                .get_solution_character(index)
+                .is_some_and(|solution| answer.chars().eq(std::iter::once(solution))),
+        }
+    }
+
     pub fn left(&self, index: usize) -> Option<char> {
         if self.col(index) == 0 {
             None
@@ -97,7 +163,7 @@ impl<'a> Grid<'a> {
             };
 
             if Square::is_black_square(character) {
-                return len + 1;
+                return len;
             }
         }
 
@@ -115,7 +181,7 @@ impl<'a> Grid<'a> {
                 };
 
             if Square::is_black_square(character) {
-                return len + 1;
+                return len;
             }
         }
 
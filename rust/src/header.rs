@@ -1,4 +1,6 @@
 use crate::data_checksum::data_checksum;
+use crate::error::PuzError;
+use crate::puzzle_buffer::{read_bytes, read_u16, read_u64};
 use crate::puzzle_type::PuzzleType;
 use crate::solution_state::SolutionState;
 use anyhow::{Context, Error, Result};
@@ -6,13 +8,23 @@ use encoding::all::{ISO_8859_1, UTF_8};
 use encoding::{DecoderTrap, EncoderTrap, Encoding};
 use std::convert::TryFrom;
 
-#[derive(Debug)]
+/// Decodes a string from the file's bytes using its version's encoding
+pub type Decoder = fn(&[u8]) -> Result<String>;
+/// Encodes a string to bytes using the file's version's encoding
+pub type Encoder = fn(&str) -> Result<Vec<u8>>;
+
+#[derive(Debug, Clone)]
 pub struct Header {
     pub global_checksum: u16,
     pub header_checksum: u16,
     pub magic_checksum: u64,
     pub file_version: String,
+    /// the byte following the 3 character version, usually a null but
+    /// sometimes garbage; kept for round-tripping
+    pub(crate) file_version_trailer: u8,
+    pub(crate) unknown1: [u8; 2],
     pub scrambled_checksum: u16,
+    pub(crate) unknown2: [u8; 12],
 
     pub width: usize,
     pub height: usize,
@@ -22,92 +34,106 @@ pub struct Header {
 }
 
 impl Header {
-    pub fn from_cursor<T: AsRef<[u8]>>(reader: &mut std::io::Cursor<T>) -> Result<Header> {
-        use byteorder::{LittleEndian, ReadBytesExt};
-        use std::io::Read;
+    /// Creates the header of a new, unlocked puzzle. Checksums are left at
+    /// 0 and are expected to be calculated once the puzzle is complete.
+    pub fn new(width: usize, height: usize, clue_count: usize) -> Header {
+        Header {
+            global_checksum: 0,
+            header_checksum: 0,
+            magic_checksum: 0,
+            file_version: "1.3".to_string(),
+            file_version_trailer: 0,
+            unknown1: [0; 2],
+            scrambled_checksum: 0,
+            unknown2: [0; 12],
+
+            width,
+            height,
+            clue_count,
+            puzzle_type: PuzzleType::Normal,
+            solution_state: SolutionState::Unlocked,
+        }
+    }
 
+    pub fn from_cursor<T: AsRef<[u8]>>(
+        reader: &mut std::io::Cursor<T>,
+    ) -> std::result::Result<Header, PuzError> {
         // H
-        let global_checksum = reader
-            .read_u16::<LittleEndian>()
-            .map_err(|_e| Error::msg("Failed to parse global checksum"))?;
+        let global_checksum = read_u16(reader, "global checksum")?;
         // 11s
-        let mut across_down = [0u8; 11];
-        reader
-            .read_exact(&mut across_down)
-            .map_err(|_e| Error::msg("Failed to parse ACROSS&DOWN"))?;
+        read_bytes(reader, 11, "ACROSS&DOWN")?;
         // x
-        let _ = reader
-            .read_u8()
-            .map_err(|_e| Error::msg("Failed to parse pad byte"))?;
+        read_bytes(reader, 1, "pad byte")?;
 
         // H
-        let header_checksum = reader
-            .read_u16::<LittleEndian>()
-            .map_err(|_e| Error::msg("Failed to parse header checksum"))?;
+        let header_checksum = read_u16(reader, "header checksum")?;
 
         // Q
-        let magic_checksum = reader.read_u64::<LittleEndian>()?;
+        let magic_checksum = read_u64(reader, "magic checksum")?;
 
         // 4s
-        let mut file_version = [0u8; 4];
-        reader
-            .read_exact(&mut file_version)
-            .map_err(|_e| Error::msg("Failed to parse file version"))?;
-
-        let file_version = std::str::from_utf8(&file_version)
-            .map_err(|_e| Error::msg("Failed to parse file version"))?[..3]
+        let version_offset = reader.position() as usize;
+        let file_version = read_bytes(reader, 4, "file version")?;
+
+        let file_version_trailer = file_version[3];
+        let file_version = std::str::from_utf8(&file_version[..3])
+            .map_err(|error| PuzError::InvalidField {
+                field: "file version".to_string(),
+                offset: version_offset,
+                message: error.to_string(),
+            })?
             .to_string();
 
         // 2s unknown 1
-        let mut unknown = [0u8; 2];
-        reader
-            .read_exact(&mut unknown)
-            .map_err(|_e| Error::msg("Failed to parse unknown bytes"))?;
+        let mut unknown1 = [0u8; 2];
+        unknown1.copy_from_slice(&read_bytes(reader, 2, "unknown bytes")?);
 
         // H
-        let scrambled_checksum = reader
-            .read_u16::<LittleEndian>()
-            .map_err(|_e| Error::msg("Failed to parse scrambled checksum"))?;
+        let scrambled_checksum = read_u16(reader, "scrambled checksum")?;
 
         // 12s unknown 2
-        let mut unknown = [0u8; 12];
-        reader
-            .read_exact(&mut unknown)
-            .map_err(|_e| Error::msg("Failed to parse second set of unknown bytes"))?;
+        let mut unknown2 = [0u8; 12];
+        unknown2.copy_from_slice(&read_bytes(reader, 12, "second set of unknown bytes")?);
 
         // B
-        let width = reader.read_u8().context("Failed to parse width")? as usize;
+        let width = read_bytes(reader, 1, "width")?[0] as usize;
         // B
-        let height = reader.read_u8().context("Failed to parse height")? as usize;
+        let height = read_bytes(reader, 1, "height")?[0] as usize;
 
         // H
-        let clue_count = reader
-            .read_u16::<LittleEndian>()
-            .context("Failed to parse clue count")? as usize;
+        let clue_count = read_u16(reader, "clue count")? as usize;
 
         // H
-        let puzzle_type = reader
-            .read_u16::<LittleEndian>()
-            .context("Failed to parse puzzle type")?;
+        let puzzle_type_offset = reader.position() as usize;
+        let puzzle_type = read_u16(reader, "puzzle type")?;
 
-        let puzzle_type = PuzzleType::try_from(puzzle_type)
-            .map_err(|_e| Error::msg(format!("{} is not a known puzzle type", puzzle_type)))?;
+        let puzzle_type =
+            PuzzleType::try_from(puzzle_type).map_err(|_e| PuzError::InvalidField {
+                field: "puzzle type".to_string(),
+                offset: puzzle_type_offset,
+                message: format!("{} is not a known puzzle type", puzzle_type),
+            })?;
 
         // H
-        let solution_state = reader
-            .read_u16::<LittleEndian>()
-            .context("Failed to parse solution state")?;
+        let solution_state_offset = reader.position() as usize;
+        let solution_state = read_u16(reader, "solution state")?;
 
-        let solution_state = SolutionState::try_from(solution_state).map_err(|_e| {
-            Error::msg(format!("{} is not a known solution state", solution_state))
-        })?;
+        let solution_state =
+            SolutionState::try_from(solution_state).map_err(|_e| PuzError::InvalidField {
+                field: "solution state".to_string(),
+                offset: solution_state_offset,
+                message: format!("{} is not a known solution state", solution_state),
+            })?;
 
         Ok(Header {
             global_checksum,
             header_checksum,
             magic_checksum,
             file_version,
+            file_version_trailer,
+            unknown1,
             scrambled_checksum,
+            unknown2,
 
             width,
             height,
@@ -119,7 +145,7 @@ impl Header {
 
     /// Parse file version as (major, minor) tuple
     pub fn version_tuple(&self) -> Result<(u64, u64)> {
-        let split = self.file_version.as_str().split(".").collect::<Vec<_>>();
+        let split = self.file_version.as_str().split('.').collect::<Vec<_>>();
 
         if split.len() != 2 {
             return Err(Error::msg(format!(
@@ -139,7 +165,7 @@ impl Header {
         Ok((major, minor))
     }
 
-    pub fn get_decoder(&self) -> Result<fn(&[u8]) -> Result<String>> {
+    pub fn get_decoder(&self) -> Result<Decoder> {
         if self.version_tuple()?.0 < 2 {
             Ok(|bytes| {
                 ISO_8859_1
@@ -159,7 +185,7 @@ impl Header {
         }
     }
 
-    pub fn get_encoder(&self) -> Result<fn(&str) -> Result<Vec<u8>>> {
+    pub fn get_encoder(&self) -> Result<Encoder> {
         if self.version_tuple()?.0 < 2 {
             Ok(|string| {
                 ISO_8859_1
@@ -177,21 +203,110 @@ impl Header {
         }
     }
 
+    /// The width, height and clue count at the sizes they're stored with,
+    /// failing rather than truncating any that don't fit
+    fn packed_sizes(&self) -> Result<(u8, u8, u16)> {
+        let too_large = |field: &str, value: usize| {
+            Error::msg(format!(
+                "The {} of {} is too large to store in a puz file",
+                field, value
+            ))
+        };
+
+        Ok((
+            u8::try_from(self.width).map_err(|_e| too_large("width", self.width))?,
+            u8::try_from(self.height).map_err(|_e| too_large("height", self.height))?,
+            u16::try_from(self.clue_count)
+                .map_err(|_e| too_large("clue count", self.clue_count))?,
+        ))
+    }
+
     pub fn calculate_checksum(&self) -> Result<u16> {
         use byteorder::{LittleEndian, WriteBytesExt};
         // pack ('<BBH H H ')
         let mut buffer = vec![];
 
+        let (width, height, clue_count) = self.packed_sizes()?;
+
+        buffer.write_u8(width).context("Failed to pack width")?;
+
+        buffer.write_u8(height).context("Failed to pack height")?;
+
+        buffer
+            .write_u16::<LittleEndian>(clue_count)
+            .context("Failed to pack clue count")?;
+
+        buffer
+            .write_u16::<LittleEndian>(self.puzzle_type.into())
+            .context("Failed to pack puzzle type")?;
+
+        buffer
+            .write_u16::<LittleEndian>(self.solution_state.into())
+            .context("Failed to pack solution state")?;
+
+        Ok(data_checksum(&buffer, 0))
+    }
+
+    /// Packs the header into its on-disk representation. Checksums are
+    /// written as they are stored on the header, so they should be
+    /// recalculated beforehand if the puzzle has changed.
+    pub fn pack(&self) -> Result<Vec<u8>> {
+        use byteorder::{LittleEndian, WriteBytesExt};
+        use std::io::Write;
+        // pack ('<H 11s xH Q 4s 2s H 12s BBH H H ')
+        let mut buffer = vec![];
+
+        buffer
+            .write_u16::<LittleEndian>(self.global_checksum)
+            .context("Failed to pack global checksum")?;
+
+        buffer
+            .write_all(b"ACROSS&DOWN\0")
+            .context("Failed to pack ACROSS&DOWN")?;
+
+        buffer
+            .write_u16::<LittleEndian>(self.header_checksum)
+            .context("Failed to pack header checksum")?;
+
+        buffer
+            .write_u64::<LittleEndian>(self.magic_checksum)
+            .context("Failed to pack magic checksum")?;
+
+        if self.file_version.len() != 3 {
+            return Err(Error::msg(format!(
+                "Expected 3 character file version; received {:?}",
+                self.file_version
+            )));
+        }
+
+        buffer
+            .write_all(self.file_version.as_bytes())
+            .context("Failed to pack file version")?;
+
+        buffer
+            .write_u8(self.file_version_trailer)
+            .context("Failed to pack file version")?;
+
         buffer
-            .write_u8(self.width as u8)
-            .context("Failed to pack width")?;
+            .write_all(&self.unknown1)
+            .context("Failed to pack unknown bytes")?;
 
         buffer
-            .write_u8(self.height as u8)
-            .context("Failed to pack height")?;
+            .write_u16::<LittleEndian>(self.scrambled_checksum)
+            .context("Failed to pack scrambled checksum")?;
 
         buffer
-            .write_u16::<LittleEndian>(self.clue_count as u16)
+            .write_all(&self.unknown2)
+            .context("Failed to pack second set of unknown bytes")?;
+
+        let (width, height, clue_count) = self.packed_sizes()?;
+
+        buffer.write_u8(width).context("Failed to pack width")?;
+
+        buffer.write_u8(height).context("Failed to pack height")?;
+
+        buffer
+            .write_u16::<LittleEndian>(clue_count)
             .context("Failed to pack clue count")?;
 
         buffer
@@ -202,6 +317,6 @@ impl Header {
             .write_u16::<LittleEndian>(self.solution_state.into())
             .context("Failed to pack solution state")?;
 
-        Ok(data_checksum(&buffer, 0))
+        Ok(buffer)
     }
 }
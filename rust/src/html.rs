@@ -0,0 +1,144 @@
+use crate::clues::Clue;
+use crate::svg::{Letters, SvgOptions};
+use crate::xml::escape;
+use crate::Puzzle;
+use anyhow::Result;
+use serde::Deserialize;
+use std::fmt::Write;
+
+const STYLE: &str = "
+body { font-family: Helvetica, Arial, sans-serif; margin: 2em; }
+h1 { margin: 0; font-size: 1.6em; }
+header p { margin: 0.2em 0; }
+.grid { margin: 1em 0; }
+.grid svg { max-width: 100%; height: auto; }
+.clues { column-count: 2; column-gap: 2em; font-size: 0.9em; }
+.clues h2 { font-size: 1.1em; margin: 0 0 0.4em; }
+.clues ol { list-style: none; padding: 0; margin: 0 0 1em; }
+.clues li { break-inside: avoid; margin-bottom: 0.2em; }
+.clues .number { display: inline-block; min-width: 2em; font-weight: bold; }
+.answer-key { break-before: page; page-break-before: always; }
+@media print { body { margin: 0; } }
+";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct HtmlOptions {
+    /// adds a page with the solved grid after the clues
+    #[serde(rename = "answerKey")]
+    pub answer_key: bool,
+}
+
+fn clue_list(html: &mut String, heading: &str, clues: &[Clue]) {
+    let _ = writeln!(html, "<section>\n<h2>{}</h2>\n<ol>", heading);
+    for clue in clues {
+        let _ = writeln!(
+            html,
+            "<li value=\"{}\"><span class=\"number\">{}</span>{}</li>",
+            clue.clue_number,
+            clue.clue_number,
+            escape(&clue.text)
+        );
+    }
+    html.push_str("</ol>\n</section>\n");
+}
+
+impl Puzzle {
+    /// Lays the puzzle out as a standalone HTML page for printing: the title
+    /// and author, a blank grid and the clues in two columns, optionally
+    /// followed by an answer key on its own page.
+    pub fn to_html(&self, options: &HtmlOptions) -> Result<String> {
+        if options.answer_key {
+            self.ensure_unlocked("an answer key can be printed")?;
+        }
+
+        let title = if self.title.is_empty() {
+            "Crossword"
+        } else {
+            &self.title
+        };
+
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        let _ = writeln!(html, "<title>{}</title>", escape(title));
+        let _ = writeln!(html, "<style>{}</style>", STYLE);
+        html.push_str("</head>\n<body>\n<header>\n");
+        let _ = writeln!(html, "<h1>{}</h1>", escape(title));
+        for line in [&self.author, &self.copyright] {
+            if !line.is_empty() {
+                let _ = writeln!(html, "<p>{}</p>", escape(line));
+            }
+        }
+        html.push_str("</header>\n");
+
+        let _ = write!(
+            html,
+            "<div class=\"grid\">\n{}</div>\n",
+            self.to_svg(&SvgOptions::default())?
+        );
+
+        html.push_str("<div class=\"clues\">\n");
+        clue_list(&mut html, "Across", &self.clues.across);
+        clue_list(&mut html, "Down", &self.clues.down);
+        html.push_str("</div>\n");
+
+        if !self.notes.is_empty() {
+            let _ = writeln!(
+                html,
+                "<p class=\"notes\">{}</p>",
+                escape(&self.notes).replace('\n', "<br>\n")
+            );
+        }
+
+        if options.answer_key {
+            let _ = write!(
+                html,
+                "<section class=\"answer-key\">\n<h2>Answer key: {}</h2>\n<div class=\"grid\">\n{}</div>\n</section>\n",
+                escape(title),
+                self.to_svg(&SvgOptions {
+                    letters: Letters::Solution,
+                    ..SvgOptions::default()
+                })?
+            );
+        }
+
+        html.push_str("</body>\n</html>\n");
+
+        Ok(html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HtmlOptions;
+    use crate::test_fixtures::read_fixture;
+    use crate::xml::escape;
+    use anyhow::Result;
+
+    #[test]
+    fn test_to_html() -> Result<()> {
+        let puzzle = read_fixture("washpost.puz")?;
+
+        let html = puzzle.to_html(&HtmlOptions::default())?;
+        assert!(html.contains(&format!("<h1>{}</h1>", escape(&puzzle.title))));
+        assert_eq!(
+            html.matches("<li ").count(),
+            puzzle.clues.across.len() + puzzle.clues.down.len()
+        );
+        assert!(html.contains(&format!(
+            "<li value=\"{}\">",
+            puzzle.clues.down.last().unwrap().clue_number
+        )));
+        assert!(!html.contains("answer-key\">"));
+
+        let with_key = puzzle.to_html(&HtmlOptions { answer_key: true })?;
+        assert!(with_key.contains("<section class=\"answer-key\">"));
+        assert_eq!(with_key.matches("<svg").count(), 2);
+
+        let locked = read_fixture("nyt_locked.puz")?;
+        assert!(locked.to_html(&HtmlOptions::default()).is_ok());
+        assert!(locked.to_html(&HtmlOptions { answer_key: true }).is_err());
+
+        Ok(())
+    }
+}
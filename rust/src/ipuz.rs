@@ -0,0 +1,537 @@
+use crate::clues::{Clues, Direction};
+use crate::grid::Grid;
+use crate::markup::SquareFlags;
+use crate::puzzle_type::PuzzleType;
+use crate::square::Square;
+use crate::Puzzle;
+use anyhow::{Context, Error, Result};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+const CROSSWORD_KIND: &str = "http://ipuz.org/crossword#1";
+const DIAGRAMLESS_KIND: &str = "http://ipuz.org/crossword/diagramless#1";
+const DEFAULT_BLOCK: &str = "#";
+
+/// Returns true if an ipuz cell value is the block (black square) value.
+/// Puzzle cells can give it as an object's "cell", and solution and saved
+/// cells as an object's "value".
+fn is_block(cell: &Value, block: &str) -> bool {
+    match cell {
+        Value::String(string) => string == block,
+        Value::Object(object) => ["cell", "value"]
+            .iter()
+            .any(|key| object.get(*key).is_some_and(|cell| is_block(cell, block))),
+        _ => false,
+    }
+}
+
+/// Returns the text of a solution or saved cell, which may be a string or
+/// an object with a "value"
+fn cell_text(cell: &Value) -> Option<&str> {
+    match cell {
+        Value::String(string) => Some(string),
+        Value::Object(object) => object.get("value").and_then(Value::as_str),
+        _ => None,
+    }
+}
+
+fn is_circled(cell: &Value) -> bool {
+    cell.get("style")
+        .and_then(|style| style.get("shapebg"))
+        .and_then(Value::as_str)
+        == Some("circle")
+}
+
+fn parse_clue_number(number: &Value) -> Option<usize> {
+    match number {
+        Value::Number(number) => number.as_u64().map(|number| number as usize),
+        Value::String(string) => string.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parses a clue, which is either [number, text] or an object with
+/// "number" and "clue"
+fn parse_clue(clue: &Value) -> Result<(usize, String)> {
+    let (number, text) = match clue {
+        Value::Array(array) if array.len() == 2 => (&array[0], &array[1]),
+        Value::Object(object) => (
+            object.get("number").unwrap_or(&Value::Null),
+            object.get("clue").unwrap_or(&Value::Null),
+        ),
+        _ => return Err(Error::msg(format!("Unsupported clue {}", clue))),
+    };
+
+    let number = parse_clue_number(number)
+        .ok_or_else(|| Error::msg(format!("Clue {} has no number", clue)))?;
+    let text = text
+        .as_str()
+        .ok_or_else(|| Error::msg(format!("Clue {} has no text", clue)))?;
+
+    Ok((number, text.to_string()))
+}
+
+fn parse_clues(clues: Option<&Value>) -> Result<HashMap<(Direction, usize), String>> {
+    let clues = match clues {
+        Some(Value::Object(clues)) => clues,
+        Some(_) => return Err(Error::msg("Expected clues to be an object")),
+        None => return Ok(HashMap::new()),
+    };
+
+    let mut parsed = HashMap::new();
+    for (direction, list) in clues {
+        // directions may be given as "Across:Label"
+        let direction = match direction.split(':').next() {
+            Some("Across") => Direction::Across,
+            Some("Down") => Direction::Down,
+            _ => {
+                return Err(Error::msg(format!(
+                    "{} clues cannot be represented in a puz file",
+                    direction
+                )))
+            }
+        };
+
+        let list = list
+            .as_array()
+            .ok_or_else(|| Error::msg("Expected a list of clues"))?;
+
+        for clue in list {
+            let (number, text) = parse_clue(clue)?;
+            parsed.insert((direction, number), text);
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn rows(value: Option<&Value>, name: &str, width: usize, height: usize) -> Result<Vec<Value>> {
+    let rows = value
+        .and_then(Value::as_array)
+        .ok_or_else(|| Error::msg(format!("Expected {} to be a list of rows", name)))?;
+
+    if rows.len() != height {
+        return Err(Error::msg(format!(
+            "Expected {} rows in {}; received {}",
+            height,
+            name,
+            rows.len()
+        )));
+    }
+
+    let mut cells = vec![];
+    for row in rows {
+        let row = row
+            .as_array()
+            .ok_or_else(|| Error::msg(format!("Expected rows of {} to be lists", name)))?;
+
+        if row.len() != width {
+            return Err(Error::msg(format!(
+                "Expected {} squares in each row of {}; received {}",
+                width,
+                name,
+                row.len()
+            )));
+        }
+
+        cells.extend(row.iter().cloned());
+    }
+
+    Ok(cells)
+}
+
+impl Puzzle {
+    /// Reads a crossword in the ipuz (JSON) format. Features that can't be
+    /// stored in a puz file, such as omitted squares, bars and clues in other
+    /// directions, are rejected.
+    pub fn from_ipuz(data: &str) -> Result<Puzzle> {
+        let ipuz: Value = serde_json::from_str(data).context("Failed to parse ipuz JSON")?;
+
+        let kinds = ipuz
+            .get("kind")
+            .and_then(Value::as_array)
+            .ok_or_else(|| Error::msg("ipuz is missing its kind"))?;
+        let kind_is = |prefix: &str| {
+            kinds
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|kind| kind.starts_with(prefix))
+        };
+        let puzzle_type = if kind_is("http://ipuz.org/crossword/diagramless") {
+            PuzzleType::Diagramless
+        } else if kind_is("http://ipuz.org/crossword") {
+            PuzzleType::Normal
+        } else {
+            return Err(Error::msg(format!(
+                "Only crosswords can be represented in a puz file; received {:?}",
+                kinds
+            )));
+        };
+
+        let dimension = |name: &str| {
+            ipuz.get("dimensions")
+                .and_then(|dimensions| dimensions.get(name))
+                .and_then(Value::as_u64)
+                .map(|dimension| dimension as usize)
+                .ok_or_else(|| Error::msg(format!("ipuz is missing its {}", name)))
+        };
+        let width = dimension("width")?;
+        let height = dimension("height")?;
+
+        let block = ipuz
+            .get("block")
+            .and_then(Value::as_str)
+            .unwrap_or(DEFAULT_BLOCK);
+
+        let cells = rows(ipuz.get("puzzle"), "puzzle", width, height)?;
+        let solution_cells = rows(ipuz.get("solution"), "solution", width, height)
+            .context("puz files require a solution")?;
+        let saved_cells = match ipuz.get("saved") {
+            Some(saved) => Some(rows(Some(saved), "saved", width, height)?),
+            None => None,
+        };
+
+        // diagramless puz files mark black squares with ':'
+        let black = match puzzle_type {
+            PuzzleType::Normal => Square::black(),
+            PuzzleType::Diagramless => ':',
+        };
+
+        let mut solution = String::new();
+        let mut rebus = vec![];
+        for (index, (cell, solution_cell)) in cells.iter().zip(&solution_cells).enumerate() {
+            if cell.is_null() || cell.get("cell").is_some_and(Value::is_null) {
+                return Err(Error::msg(format!(
+                    "Square {} is omitted, which a puz file cannot represent",
+                    index
+                )));
+            }
+
+            if cell
+                .get("style")
+                .and_then(|style| style.get("barred"))
+                .is_some()
+            {
+                return Err(Error::msg(format!(
+                    "Square {} has bars, which a puz file cannot represent",
+                    index
+                )));
+            }
+
+            if is_block(cell, block) || is_block(solution_cell, block) {
+                solution.push(black);
+                continue;
+            }
+
+            let answer = cell_text(solution_cell)
+                .filter(|answer| !answer.is_empty())
+                .ok_or_else(|| Error::msg(format!("Square {} has no solution", index)))?;
+
+            let mut characters = answer.chars();
+            solution.push(characters.next().expect("answer is not empty"));
+            if characters.next().is_some() {
+                rebus.push((index, answer.to_string()));
+            }
+        }
+
+        let clue_texts = parse_clues(ipuz.get("clues"))?;
+        let numbering = Clues::numbering(Grid::new(&solution, &solution, width, height));
+        let all_clues = numbering
+            .in_puz_order()
+            .map(|(direction, clue)| {
+                clue_texts
+                    .get(&(direction, clue.clue_number))
+                    .cloned()
+                    .ok_or_else(|| {
+                        Error::msg(format!(
+                            "Missing clue for {} {:?}",
+                            clue.clue_number, direction
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<String>>>()?;
+
+        if all_clues.len() != clue_texts.len() {
+            return Err(Error::msg(format!(
+                "The grid has {} clues but the ipuz has {}; clue numbering that differs from the grid cannot be represented in a puz file",
+                all_clues.len(),
+                clue_texts.len()
+            )));
+        }
+
+        let mut puzzle = Puzzle::new(width, height, &solution, all_clues)?;
+        puzzle.header.puzzle_type = puzzle_type;
+
+        let text = |name: &str| {
+            ipuz.get(name)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        puzzle.title = text("title");
+        puzzle.author = text("author");
+        puzzle.copyright = text("copyright");
+        puzzle.notes = text("notes");
+
+        for (index, answer) in rebus {
+            puzzle.rebus.set(index, &answer)?;
+        }
+
+        for (index, cell) in cells.iter().enumerate() {
+            if is_circled(cell) {
+                let mut flags = SquareFlags::default();
+                flags.set_circled(true);
+                puzzle.markup.set(index, flags)?;
+            }
+        }
+
+        if let Some(saved_cells) = saved_cells {
+            for (index, saved) in saved_cells.iter().enumerate() {
+                let entry = match cell_text(saved) {
+                    Some(entry) if !entry.is_empty() && !is_block(saved, block) => entry,
+                    _ => continue,
+                };
+
+                puzzle.set_rebus_fill(index / width, index % width, entry)?;
+            }
+        }
+
+        puzzle.upgrade_version_for_text();
+        puzzle.refresh_checksums()?;
+
+        Ok(puzzle)
+    }
+
+    /// Writes the puzzle in the ipuz (JSON) format
+    pub fn to_ipuz(&self) -> Result<String> {
+        self.ensure_unlocked("they can be written as ipuz")?;
+
+        let grid = self.grid();
+        let mut numbers = HashMap::new();
+        for clue in self.clues.across.iter().chain(&self.clues.down) {
+            numbers.insert(clue.row * self.width() + clue.column, clue.clue_number);
+        }
+
+        let mut puzzle = vec![];
+        let mut solution = vec![];
+        let mut saved = vec![];
+        for row in 0..self.height() {
+            let mut puzzle_row = vec![];
+            let mut solution_row = vec![];
+            let mut saved_row = vec![];
+
+            for column in 0..self.width() {
+                let index = row * self.width() + column;
+                let square = grid
+                    .get_solution_character(index)
+                    .ok_or_else(|| Error::msg(format!("Square {} has no solution", index)))?;
+
+                if Square::is_black_square(square) {
+                    puzzle_row.push(json!(DEFAULT_BLOCK));
+                    solution_row.push(json!(DEFAULT_BLOCK));
+                    saved_row.push(json!(DEFAULT_BLOCK));
+                    continue;
+                }
+
+                let number = json!(numbers.get(&index).copied().unwrap_or(0));
+                puzzle_row.push(if grid.get_flags(index).is_circled() {
+                    json!({ "cell": number, "style": { "shapebg": "circle" } })
+                } else {
+                    number
+                });
+
+                solution_row.push(json!(grid.get_solution(index)));
+
+                let fill = grid.get_fill_character(index).unwrap_or('-');
+                saved_row.push(match self.rebus_fill.get(index) {
+                    Some(entry) => json!(entry),
+                    None if fill == '-' => json!(""),
+                    None => json!(fill.to_string()),
+                });
+            }
+
+            puzzle.push(Value::Array(puzzle_row));
+            solution.push(Value::Array(solution_row));
+            saved.push(Value::Array(saved_row));
+        }
+
+        let clue_list = |clues: &[crate::Clue]| {
+            clues
+                .iter()
+                .map(|clue| json!([clue.clue_number, clue.text]))
+                .collect::<Vec<_>>()
+        };
+
+        let mut ipuz = Map::new();
+        ipuz.insert("version".to_string(), json!("http://ipuz.org/v2"));
+        ipuz.insert(
+            "kind".to_string(),
+            json!([match self.header.puzzle_type {
+                PuzzleType::Normal => CROSSWORD_KIND,
+                PuzzleType::Diagramless => DIAGRAMLESS_KIND,
+            }]),
+        );
+        ipuz.insert(
+            "dimensions".to_string(),
+            json!({ "width": self.width(), "height": self.height() }),
+        );
+        for (name, text) in [
+            ("title", &self.title),
+            ("author", &self.author),
+            ("copyright", &self.copyright),
+            ("notes", &self.notes),
+        ] {
+            if !text.is_empty() {
+                ipuz.insert(name.to_string(), json!(text));
+            }
+        }
+        ipuz.insert("block".to_string(), json!(DEFAULT_BLOCK));
+        ipuz.insert("empty".to_string(), json!(0));
+        ipuz.insert("puzzle".to_string(), Value::Array(puzzle));
+        ipuz.insert("solution".to_string(), Value::Array(solution));
+        ipuz.insert("saved".to_string(), Value::Array(saved));
+        ipuz.insert(
+            "clues".to_string(),
+            json!({
+                "Across": clue_list(&self.clues.across),
+                "Down": clue_list(&self.clues.down),
+            }),
+        );
+
+        serde_json::to_string_pretty(&Value::Object(ipuz)).context("Failed to write ipuz JSON")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::read_fixture;
+    use crate::{Puzzle, PuzzleType};
+    use anyhow::Result;
+    use serde_json::{json, Value};
+
+    const ZACK_SOLUTION: &str = "XKCD.RURALAMALEYATES.REKT";
+
+    /// zack.puz as ipuz JSON, for the tests to edit
+    fn zack_ipuz() -> Result<Value> {
+        Ok(serde_json::from_str(&read_fixture("zack.puz")?.to_ipuz()?)?)
+    }
+
+    fn from_value(ipuz: &Value) -> Result<Puzzle> {
+        Puzzle::from_ipuz(&ipuz.to_string())
+    }
+
+    #[test]
+    fn test_ipuz_round_trip() -> Result<()> {
+        let puzzle = read_fixture("nyt_rebus_with_notes_and_shape.puz")?;
+        let reparsed = Puzzle::from_ipuz(&puzzle.to_ipuz()?)?;
+
+        assert_eq!(reparsed.title, puzzle.title);
+        assert_eq!(reparsed.author, puzzle.author);
+        assert_eq!(reparsed.copyright, puzzle.copyright);
+        assert_eq!(reparsed.notes, puzzle.notes);
+        assert_eq!(reparsed.solution, puzzle.solution);
+        assert_eq!(reparsed.fill, puzzle.fill);
+        assert_eq!(reparsed.all_clues, puzzle.all_clues);
+        assert_eq!(reparsed.header.puzzle_type, PuzzleType::Normal);
+        assert_eq!(reparsed.rebus.get(22), Some("STAR"));
+        assert_eq!(
+            reparsed.grid().get_flags(48).is_circled(),
+            puzzle.grid().get_flags(48).is_circled()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipuz_cell_forms() -> Result<()> {
+        let zack = read_fixture("zack.puz")?;
+
+        // blocks and answers can be given as objects, clues as objects or
+        // with their number as a string, and directions with a label
+        let mut ipuz = zack_ipuz()?;
+        ipuz["puzzle"][0][4] = json!({ "cell": "#" });
+        ipuz["solution"][0][4] = json!({ "value": "#" });
+        ipuz["solution"][0][0] = json!({ "value": "X" });
+        ipuz["saved"][0][0] = json!("X");
+        ipuz["saved"][1][0] = json!({ "value": "RU" });
+        ipuz["saved"][4][0] = json!({ "value": "#" });
+        ipuz["clues"]["Across"][2] = json!({ "number": 7, "clue": zack.all_clues[7] });
+        ipuz["clues"]["Down"][1] = json!(["2", zack.all_clues[2]]);
+        let down = ipuz["clues"]["Down"].take();
+        ipuz["clues"]["Down:Vertical"] = down;
+        ipuz["clues"].as_object_mut().unwrap().remove("Down");
+
+        let puzzle = from_value(&ipuz)?;
+        assert_eq!(puzzle.solution, ZACK_SOLUTION);
+        assert_eq!(puzzle.all_clues, zack.all_clues);
+        assert_eq!(puzzle.fill, "X---.R--------------.----");
+        assert_eq!(puzzle.get_rebus_fill(1, 0), Some("RU"));
+
+        // and the block can be something other than #
+        let mut ipuz = zack_ipuz()?;
+        ipuz["block"] = json!("@");
+        for (row, column) in [(0, 4), (4, 0)] {
+            ipuz["puzzle"][row][column] = json!("@");
+            ipuz["solution"][row][column] = json!("@");
+            ipuz["saved"][row][column] = json!("@");
+        }
+        assert_eq!(from_value(&ipuz)?.solution, ZACK_SOLUTION);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipuz_diagramless() -> Result<()> {
+        let mut ipuz = zack_ipuz()?;
+        ipuz["kind"] = json!(["http://ipuz.org/crossword/diagramless#1"]);
+
+        let puzzle = from_value(&ipuz)?;
+        assert_eq!(puzzle.header.puzzle_type, PuzzleType::Diagramless);
+        assert_eq!(puzzle.solution, ZACK_SOLUTION.replace('.', ":"));
+
+        let mut puzzle = read_fixture("nyt_diagramless.puz")?;
+        puzzle.unlock(3285)?;
+        let reparsed = Puzzle::from_ipuz(&puzzle.to_ipuz()?)?;
+        assert_eq!(reparsed.header.puzzle_type, PuzzleType::Diagramless);
+        assert_eq!(reparsed.solution, puzzle.solution);
+        assert_eq!(reparsed.fill, puzzle.fill);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_ipuz() -> Result<()> {
+        let mut acrostic = zack_ipuz()?;
+        acrostic["kind"] = json!(["http://ipuz.org/acrostic#1"]);
+        assert!(from_value(&acrostic).is_err());
+
+        let mut diagonal = zack_ipuz()?;
+        diagonal["clues"]["Diagonal"] = json!([[1, "Slant"]]);
+        assert!(from_value(&diagonal).is_err());
+
+        let mut omitted = zack_ipuz()?;
+        omitted["puzzle"][0][4] = Value::Null;
+        assert!(from_value(&omitted).is_err());
+
+        let mut barred = zack_ipuz()?;
+        barred["puzzle"][1][1] = json!({ "cell": 0, "style": { "barred": "R" } });
+        assert!(from_value(&barred).is_err());
+
+        let mut missing_clue = zack_ipuz()?;
+        missing_clue["clues"]["Down"].as_array_mut().unwrap().pop();
+        assert!(from_value(&missing_clue).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unicode_ipuz() -> Result<()> {
+        let puzzle = read_fixture("unicode.puz")?;
+        let reparsed = Puzzle::from_ipuz(&puzzle.to_ipuz()?)?;
+
+        assert_eq!(reparsed.header.file_version, "2.0");
+        assert_eq!(reparsed.to_puz()?, puzzle.to_puz()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,605 @@
+use crate::clues::{Clue, Clues, Direction};
+use crate::grid::Grid;
+use crate::markup::SquareFlags;
+use crate::square::Square;
+use crate::xml::escape;
+use crate::Puzzle;
+use anyhow::{Context, Error, Result};
+use roxmltree::{Document, Node};
+use std::collections::HashMap;
+use std::io::Read;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Reads the XML out of a jpz file, which is either plain XML or a zip
+/// archive holding a single XML file
+fn jpz_xml(data: &[u8]) -> Result<String> {
+    if !data.starts_with(ZIP_MAGIC) {
+        return String::from_utf8(data.to_vec()).context("jpz XML is not valid UTF-8");
+    }
+
+    let mut archive =
+        zip::ZipArchive::new(std::io::Cursor::new(data)).context("Failed to open zipped jpz")?;
+    if archive.is_empty() {
+        return Err(Error::msg("Zipped jpz is empty"));
+    }
+
+    let mut xml = String::new();
+    archive
+        .by_index(0)
+        .context("Failed to read zipped jpz")?
+        .read_to_string(&mut xml)
+        .context("Zipped jpz XML is not valid UTF-8")?;
+
+    Ok(xml)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+/// All of the text of an element, dropping formatting such as <i> and <b>
+fn text(node: Node) -> String {
+    node.descendants()
+        .filter(Node::is_text)
+        .filter_map(|node| node.text())
+        .collect()
+}
+
+fn attribute_number(node: Node, name: &str) -> Result<usize> {
+    let value = node
+        .attribute(name)
+        .ok_or_else(|| Error::msg(format!("<{}> is missing {}", node.tag_name().name(), name)))?;
+
+    value.trim().parse().context(format!(
+        "Received non-integer {} in <{}>: {}",
+        name,
+        node.tag_name().name(),
+        value
+    ))
+}
+
+/// Parses a coordinate range such as "1-5" (or just "3") into 1-based bounds
+fn parse_range(range: &str) -> Result<(usize, usize)> {
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<usize>()
+            .context(format!("Received non-integer word range: {}", range))
+    };
+
+    match range.split_once('-') {
+        Some((start, end)) => Ok((parse(start)?, parse(end)?)),
+        None => Ok((parse(range)?, parse(range)?)),
+    }
+}
+
+/// Finds the direction and first square (as 0-based row and column) of a
+/// word, which lists its squares either as ranges or as <cells> children
+fn parse_word(word: Node) -> Result<(Direction, (usize, usize))> {
+    let squares = match (word.attribute("x"), word.attribute("y")) {
+        (Some(x), Some(y)) => {
+            let ((x_start, x_end), (y_start, y_end)) = (parse_range(x)?, parse_range(y)?);
+            vec![(y_start, x_start), (y_end, x_end)]
+        }
+        _ => word
+            .children()
+            .filter(|child| child.has_tag_name("cells"))
+            .map(|cells| Ok((attribute_number(cells, "y")?, attribute_number(cells, "x")?)))
+            .collect::<Result<Vec<_>>>()?,
+    };
+
+    let (first, last) = match (squares.first(), squares.last()) {
+        (Some(&first), Some(&last)) => (first, last),
+        _ => return Err(Error::msg("Word has no squares")),
+    };
+
+    let direction = if first.0 == last.0 {
+        Direction::Across
+    } else if first.1 == last.1 {
+        Direction::Down
+    } else {
+        return Err(Error::msg(
+            "Words that aren't across or down cannot be represented in a puz file",
+        ));
+    };
+
+    if first.0 == 0 || first.1 == 0 {
+        return Err(Error::msg("Word coordinates start at 1"));
+    }
+
+    Ok((direction, (first.0 - 1, first.1 - 1)))
+}
+
+/// Guesses the direction of a list of clues from its title
+fn clues_direction(clues: Node) -> Option<Direction> {
+    let title = child(clues, "title").map(text)?.to_lowercase();
+
+    if title.contains("across") {
+        Some(Direction::Across)
+    } else if title.contains("down") {
+        Some(Direction::Down)
+    } else {
+        None
+    }
+}
+
+/// Returns the side of a square a bar is drawn on, if the bar would
+/// separate two white squares
+fn bar_is_meaningful(
+    solution: &[char],
+    width: usize,
+    height: usize,
+    index: usize,
+    side: &str,
+) -> bool {
+    let (row, column) = (index / width, index % width);
+    let neighbor = match side {
+        "top-bar" if row > 0 => index - width,
+        "bottom-bar" if row + 1 < height => index + width,
+        "left-bar" if column > 0 => index - 1,
+        "right-bar" if column + 1 < width => index + 1,
+        _ => return false,
+    };
+
+    !Square::is_black_square(solution[neighbor])
+}
+
+impl Puzzle {
+    /// Reads a Crossword Compiler (jpz) puzzle, either plain XML or zipped.
+    /// Bars are only accepted where they fall between a white square and a
+    /// black square or the edge of the grid, since puz files can't store
+    /// them.
+    pub fn from_jpz(data: &[u8]) -> Result<Puzzle> {
+        let xml = jpz_xml(data)?;
+        let document = Document::parse(&xml).context("Failed to parse jpz XML")?;
+
+        let puzzle_node = document
+            .descendants()
+            .find(|node| node.has_tag_name("rectangular-puzzle"))
+            .ok_or_else(|| Error::msg("jpz has no <rectangular-puzzle>"))?;
+        let crossword = child(puzzle_node, "crossword")
+            .ok_or_else(|| Error::msg("Only crosswords can be represented in a puz file"))?;
+        let grid = child(crossword, "grid").ok_or_else(|| Error::msg("jpz has no <grid>"))?;
+
+        // checked before the grid is allocated, since the size is untrusted
+        let width = attribute_number(grid, "width")?;
+        let height = attribute_number(grid, "height")?;
+        if width == 0 || height == 0 || width > 255 || height > 255 {
+            return Err(Error::msg(format!(
+                "A {}x{} grid cannot be stored in a puz file",
+                width, height
+            )));
+        }
+
+        let mut squares = vec![None; width * height];
+        for cell in grid.children().filter(|child| child.has_tag_name("cell")) {
+            let (x, y) = (attribute_number(cell, "x")?, attribute_number(cell, "y")?);
+            if x == 0 || y == 0 || x > width || y > height {
+                return Err(Error::msg(format!(
+                    "Cell ({}, {}) is outside of the grid",
+                    x, y
+                )));
+            }
+
+            squares[(y - 1) * width + x - 1] = Some(cell);
+        }
+
+        let mut solution = vec![];
+        for (index, cell) in squares.iter().enumerate() {
+            let cell = cell.ok_or_else(|| {
+                Error::msg(format!("Square {} is missing from the jpz grid", index))
+            })?;
+
+            match cell.attribute("type") {
+                Some("block") => solution.push(Square::black()),
+                None | Some("letter") => {
+                    let answer = cell.attribute("solution").unwrap_or_default();
+                    solution.push(
+                        answer.chars().next().ok_or_else(|| {
+                            Error::msg(format!("Square {} has no solution", index))
+                        })?,
+                    );
+                }
+                Some(kind) => {
+                    return Err(Error::msg(format!(
+                        "Square {} is a {} cell, which a puz file cannot represent",
+                        index, kind
+                    )))
+                }
+            }
+        }
+
+        for (index, cell) in squares.iter().flatten().enumerate() {
+            for side in ["top-bar", "bottom-bar", "left-bar", "right-bar"] {
+                if cell.attribute(side) == Some("true")
+                    && !Square::is_black_square(solution[index])
+                    && bar_is_meaningful(&solution, width, height, index, side)
+                {
+                    return Err(Error::msg(format!(
+                        "Square {} has a {}, which a puz file cannot represent",
+                        index, side
+                    )));
+                }
+            }
+        }
+
+        let solution = solution.into_iter().collect::<String>();
+        let numbering = Clues::numbering(Grid::new(&solution, &solution, width, height));
+        let start_of = |direction: Direction, number: usize| {
+            let clues = match direction {
+                Direction::Across => &numbering.across,
+                Direction::Down => &numbering.down,
+            };
+
+            clues
+                .iter()
+                .find(|clue| clue.clue_number == number)
+                .map(|clue| (clue.row, clue.column))
+        };
+
+        let mut words = HashMap::new();
+        for word in crossword
+            .children()
+            .filter(|child| child.has_tag_name("word"))
+        {
+            let id = word
+                .attribute("id")
+                .ok_or_else(|| Error::msg("<word> is missing id"))?;
+            words.insert(id, parse_word(word)?);
+        }
+
+        let mut clue_texts = HashMap::new();
+        let mut clue_count = 0;
+        for clues in crossword
+            .children()
+            .filter(|child| child.has_tag_name("clues"))
+        {
+            let direction = clues_direction(clues);
+
+            for clue in clues.children().filter(|child| child.has_tag_name("clue")) {
+                let start = match clue.attribute("word").and_then(|word| words.get(word)) {
+                    Some(&start) => Some(start),
+                    None => {
+                        let number = attribute_number(clue, "number")?;
+                        direction.and_then(|direction| {
+                            start_of(direction, number).map(|start| (direction, start))
+                        })
+                    }
+                };
+
+                let start = start.ok_or_else(|| {
+                    Error::msg(format!(
+                        "Clue {:?} doesn't belong to a word in the grid",
+                        text(clue)
+                    ))
+                })?;
+
+                clue_texts.insert(start, text(clue));
+                clue_count += 1;
+            }
+        }
+
+        let all_clues = numbering
+            .in_puz_order()
+            .map(|(direction, clue)| {
+                clue_texts
+                    .get(&(direction, (clue.row, clue.column)))
+                    .cloned()
+                    .ok_or_else(|| {
+                        Error::msg(format!(
+                            "Missing clue for {} {:?}",
+                            clue.clue_number, direction
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<String>>>()?;
+
+        if all_clues.len() != clue_count {
+            return Err(Error::msg(format!(
+                "The grid has {} clues but the jpz has {}",
+                all_clues.len(),
+                clue_count
+            )));
+        }
+
+        let mut puzzle = Puzzle::new(width, height, &solution, all_clues)?;
+
+        if let Some(metadata) = child(puzzle_node, "metadata") {
+            let field = |name: &str| child(metadata, name).map(text).unwrap_or_default();
+            puzzle.title = field("title");
+            puzzle.author = field("creator");
+            puzzle.copyright = field("copyright");
+            puzzle.notes = field("description");
+        }
+
+        for (index, cell) in squares.iter().flatten().enumerate() {
+            let answer = cell.attribute("solution").unwrap_or_default();
+            if answer.chars().count() > 1 {
+                puzzle.rebus.set(index, answer)?;
+            }
+
+            if cell.attribute("background-shape") == Some("circle") {
+                let mut flags = SquareFlags::default();
+                flags.set_circled(true);
+                puzzle.markup.set(index, flags)?;
+            }
+
+            if let Some(entry) = cell
+                .attribute("solve-state")
+                .filter(|entry| !entry.is_empty())
+            {
+                puzzle.set_rebus_fill(index / width, index % width, entry)?;
+            }
+        }
+
+        puzzle.upgrade_version_for_text();
+        puzzle.refresh_checksums()?;
+
+        Ok(puzzle)
+    }
+
+    /// Writes the puzzle as plain (unzipped) Crossword Compiler XML
+    pub fn to_jpz(&self) -> Result<String> {
+        self.ensure_unlocked("they can be written as jpz")?;
+
+        let grid = self.grid();
+        let mut numbers = HashMap::new();
+        for clue in self.clues.across.iter().chain(&self.clues.down) {
+            numbers.insert(clue.row * self.width() + clue.column, clue.clue_number);
+        }
+
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<crossword-compiler-applet xmlns=\"http://crossword.info/xml/crossword-compiler-applet\">\n");
+        xml.push_str("<rectangular-puzzle xmlns=\"http://crossword.info/xml/rectangular-puzzle\" alphabet=\"ABCDEFGHIJKLMNOPQRSTUVWXYZ\">\n");
+
+        xml.push_str("<metadata>\n");
+        for (name, value) in [
+            ("title", &self.title),
+            ("creator", &self.author),
+            ("copyright", &self.copyright),
+            ("description", &self.notes),
+        ] {
+            if !value.is_empty() {
+                xml.push_str(&format!("<{}>{}</{}>\n", name, escape(value), name));
+            }
+        }
+        xml.push_str("</metadata>\n");
+
+        xml.push_str("<crossword>\n");
+        xml.push_str(&format!(
+            "<grid width=\"{}\" height=\"{}\">\n",
+            self.width(),
+            self.height()
+        ));
+        xml.push_str("<grid-look numbering-scheme=\"normal\"/>\n");
+
+        for index in 0..self.width() * self.height() {
+            let (x, y) = (grid.col(index) + 1, grid.row(index) + 1);
+            let square = grid
+                .get_solution_character(index)
+                .ok_or_else(|| Error::msg(format!("Square {} has no solution", index)))?;
+
+            if Square::is_black_square(square) {
+                xml.push_str(&format!("<cell x=\"{}\" y=\"{}\" type=\"block\"/>\n", x, y));
+                continue;
+            }
+
+            let mut cell = format!(
+                "<cell x=\"{}\" y=\"{}\" solution=\"{}\"",
+                x,
+                y,
+                escape(
+                    &grid
+                        .get_solution(index)
+                        .unwrap_or_else(|| square.to_string())
+                )
+            );
+
+            if let Some(number) = numbers.get(&index) {
+                cell.push_str(&format!(" number=\"{}\"", number));
+            }
+
+            if grid.get_flags(index).is_circled() {
+                cell.push_str(" background-shape=\"circle\"");
+            }
+
+            let fill = grid.get_fill_character(index).unwrap_or('-');
+            let entry = match self.rebus_fill.get(index) {
+                Some(entry) => entry.to_string(),
+                None if fill == '-' => String::new(),
+                None => fill.to_string(),
+            };
+            if !entry.is_empty() {
+                cell.push_str(&format!(" solve-state=\"{}\"", escape(&entry)));
+            }
+
+            cell.push_str("/>\n");
+            xml.push_str(&cell);
+        }
+        xml.push_str("</grid>\n");
+
+        let directions: [(&str, &[Clue]); 2] =
+            [("Across", &self.clues.across), ("Down", &self.clues.down)];
+
+        let mut word_id = 1;
+        let mut clue_lists = String::new();
+        for (name, clues) in directions {
+            clue_lists.push_str(&format!(
+                "<clues ordering=\"normal\"><title><b>{}</b></title>\n",
+                name
+            ));
+
+            for clue in clues {
+                let (x, y) = (clue.column + 1, clue.row + 1);
+                let (x, y) = if name == "Across" {
+                    (format!("{}-{}", x, x + clue.length - 1), y.to_string())
+                } else {
+                    (x.to_string(), format!("{}-{}", y, y + clue.length - 1))
+                };
+
+                xml.push_str(&format!(
+                    "<word id=\"{}\" x=\"{}\" y=\"{}\"/>\n",
+                    word_id, x, y
+                ));
+                clue_lists.push_str(&format!(
+                    "<clue word=\"{}\" number=\"{}\" format=\"{}\">{}</clue>\n",
+                    word_id,
+                    clue.clue_number,
+                    clue.length,
+                    escape(&clue.text)
+                ));
+
+                word_id += 1;
+            }
+
+            clue_lists.push_str("</clues>\n");
+        }
+
+        xml.push_str(&clue_lists);
+        xml.push_str("</crossword>\n");
+        xml.push_str("</rectangular-puzzle>\n");
+        xml.push_str("</crossword-compiler-applet>\n");
+
+        Ok(xml)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::read_fixture;
+    use crate::Puzzle;
+    use anyhow::Result;
+    use std::io::Write;
+
+    /// zack.puz as jpz XML, with each pattern replaced. The patterns must
+    /// all be there, so the tests can't quietly stop testing anything if
+    /// the writer changes.
+    fn zack_jpz(replacements: &[(&str, &str)]) -> Result<Vec<u8>> {
+        let mut jpz = read_fixture("zack.puz")?.to_jpz()?;
+        for (pattern, replacement) in replacements {
+            assert!(jpz.contains(pattern), "{:?} isn't in the jpz", pattern);
+            jpz = jpz.replace(pattern, replacement);
+        }
+
+        Ok(jpz.into_bytes())
+    }
+
+    #[test]
+    fn test_jpz_round_trip() -> Result<()> {
+        let puzzle = read_fixture("nyt_rebus_with_notes_and_shape.puz")?;
+        let reparsed = Puzzle::from_jpz(puzzle.to_jpz()?.as_bytes())?;
+
+        assert_eq!(reparsed.title, puzzle.title);
+        assert_eq!(reparsed.author, puzzle.author);
+        assert_eq!(reparsed.copyright, puzzle.copyright);
+        assert_eq!(reparsed.notes, puzzle.notes);
+        assert_eq!(reparsed.solution, puzzle.solution);
+        assert_eq!(reparsed.fill, puzzle.fill);
+        assert_eq!(reparsed.all_clues, puzzle.all_clues);
+        assert_eq!(reparsed.rebus.get(22), Some("STAR"));
+        assert_eq!(reparsed.markup, puzzle.markup);
+
+        let unicode = read_fixture("unicode.puz")?;
+        let reparsed = Puzzle::from_jpz(unicode.to_jpz()?.as_bytes())?;
+        assert_eq!(reparsed.all_clues, unicode.all_clues);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zipped_jpz() -> Result<()> {
+        let mut zipped = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        zipped.start_file("zack.xml", zip::write::FileOptions::default())?;
+        zipped.write_all(&zack_jpz(&[])?)?;
+        let zipped = zipped.finish()?.into_inner();
+
+        let zack = read_fixture("zack.puz")?;
+        let puzzle = Puzzle::from_jpz(&zipped)?;
+        assert_eq!(puzzle.solution, zack.solution);
+        assert_eq!(puzzle.all_clues, zack.all_clues);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jpz_clues_and_words() -> Result<()> {
+        // clues can have formatting and be matched to the grid by number
+        // instead of by word, words can list their cells, and the solver's
+        // entries are kept
+        let puzzle = Puzzle::from_jpz(&zack_jpz(&[
+            (
+                ">Fallen in Fortnite, say<",
+                "><i>Fallen</i> in Fortnite &amp; such<",
+            ),
+            (
+                "<clue word=\"8\" number=\"3\" format=\"5\">",
+                "<clue number=\"3\">",
+            ),
+            (
+                "<word id=\"5\" x=\"2-5\" y=\"5\"/>",
+                concat!(
+                    "<word id=\"5\"><cells x=\"2\" y=\"5\"/><cells x=\"3\" y=\"5\"/>",
+                    "<cells x=\"4\" y=\"5\"/><cells x=\"5\" y=\"5\"/></word>"
+                ),
+            ),
+            (
+                "solution=\"X\" number=\"1\"",
+                "solution=\"X\" number=\"1\" solve-state=\"XR\"",
+            ),
+        ])?)?;
+
+        assert_eq!(puzzle.clues.across[4].text, "Fallen in Fortnite & such");
+        assert_eq!(puzzle.clues.down[2].text, "Rust package component");
+        assert_eq!(puzzle.get_rebus_fill(0, 0), Some("XR"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jpz_bars() -> Result<()> {
+        // bars along the edge of the grid or next to a block change nothing
+        let harmless = zack_jpz(&[
+            (
+                "x=\"5\" y=\"4\" solution=\"S\"",
+                "x=\"5\" y=\"4\" solution=\"S\" right-bar=\"true\"",
+            ),
+            (
+                "x=\"4\" y=\"1\" solution=\"D\"",
+                "x=\"4\" y=\"1\" solution=\"D\" right-bar=\"true\"",
+            ),
+        ])?;
+        assert_eq!(
+            Puzzle::from_jpz(&harmless)?.solution,
+            "XKCD.RURALAMALEYATES.REKT"
+        );
+
+        let barred = zack_jpz(&[(
+            "x=\"3\" y=\"3\" solution=\"A\"",
+            "x=\"3\" y=\"3\" solution=\"A\" left-bar=\"true\"",
+        )])?;
+        assert!(Puzzle::from_jpz(&barred).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_jpz() -> Result<()> {
+        let missing_clue = zack_jpz(&[(
+            "<clue word=\"10\" number=\"6\" format=\"4\">In case</clue>",
+            "",
+        )])?;
+        assert!(Puzzle::from_jpz(&missing_clue).is_err());
+
+        let void = zack_jpz(&[("type=\"block\"", "type=\"void\"")])?;
+        assert!(Puzzle::from_jpz(&void).is_err());
+
+        for size in ["0", "256", "18446744073709551615"] {
+            let huge = zack_jpz(&[("width=\"5\"", &format!("width=\"{}\"", size))])?;
+            assert!(Puzzle::from_jpz(&huge).is_err());
+        }
+
+        Ok(())
+    }
+}
@@ -1,15 +1,48 @@
+mod across_lite_text;
+mod checksum;
 mod clues;
 mod data_checksum;
+mod error;
+mod exolve;
 mod extension;
 mod grid;
 mod header;
+mod html;
+mod ipuz;
+mod jpz;
+mod markup;
+mod parse_options;
 mod puzzle;
 mod puzzle_buffer;
 mod puzzle_type;
+mod rebus;
+mod scramble;
 mod solution_state;
 mod square;
+mod svg;
+#[cfg(test)]
+mod test_fixtures;
+mod text_grid;
+mod timer;
+mod validate;
+mod xd;
+mod xml;
 
-pub use clues::{Clue, Clues};
+pub use checksum::Checksum;
+pub use clues::{Clue, Clues, Direction};
+pub use error::PuzError;
+pub use extension::Extension;
+pub use grid::Grid;
+pub use header::Header;
+pub use html::HtmlOptions;
+pub use markup::{Markup, SquareFlags};
+pub use parse_options::{ChecksumVerification, ParseOptions};
 pub use puzzle::Puzzle;
 pub use puzzle_type::PuzzleType;
+pub use rebus::{Rebus, RebusFill};
+pub use scramble::KeyCandidate;
 pub use solution_state::SolutionState;
+pub use svg::{Letters, SvgOptions};
+pub use text_grid::TextGridOptions;
+pub use timer::Timer;
+pub use validate::{Diagnostic, Severity};
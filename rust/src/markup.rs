@@ -0,0 +1,174 @@
+use crate::extension::Extension;
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// Flags of a single square, stored as a bit field in the GEXT extension.
+/// Unknown bits are kept so files round-trip.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "SquareFlagFields", into = "SquareFlagFields")]
+pub struct SquareFlags(u8);
+
+impl SquareFlags {
+    const PREVIOUSLY_INCORRECT: u8 = 0x10;
+    const INCORRECT: u8 = 0x20;
+    const REVEALED: u8 = 0x40;
+    const CIRCLED: u8 = 0x80;
+
+    pub fn from_bits(bits: u8) -> SquareFlags {
+        SquareFlags(bits)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// square was marked incorrect at some point
+    pub fn is_previously_incorrect(&self) -> bool {
+        self.0 & Self::PREVIOUSLY_INCORRECT != 0
+    }
+
+    /// square is currently marked incorrect
+    pub fn is_incorrect(&self) -> bool {
+        self.0 & Self::INCORRECT != 0
+    }
+
+    /// square's contents were revealed to the solver
+    pub fn is_revealed(&self) -> bool {
+        self.0 & Self::REVEALED != 0
+    }
+
+    pub fn is_circled(&self) -> bool {
+        self.0 & Self::CIRCLED != 0
+    }
+
+    pub fn set_previously_incorrect(&mut self, value: bool) {
+        self.set(Self::PREVIOUSLY_INCORRECT, value);
+    }
+
+    pub fn set_incorrect(&mut self, value: bool) {
+        self.set(Self::INCORRECT, value);
+    }
+
+    pub fn set_revealed(&mut self, value: bool) {
+        self.set(Self::REVEALED, value);
+    }
+
+    pub fn set_circled(&mut self, value: bool) {
+        self.set(Self::CIRCLED, value);
+    }
+
+    fn set(&mut self, flag: u8, value: bool) {
+        if value {
+            self.0 |= flag;
+        } else {
+            self.0 &= !flag;
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SquareFlagFields {
+    circled: bool,
+    #[serde(rename = "previouslyIncorrect")]
+    previously_incorrect: bool,
+    incorrect: bool,
+    revealed: bool,
+}
+
+impl From<SquareFlagFields> for SquareFlags {
+    fn from(fields: SquareFlagFields) -> SquareFlags {
+        let mut flags = SquareFlags::default();
+        flags.set_circled(fields.circled);
+        flags.set_previously_incorrect(fields.previously_incorrect);
+        flags.set_incorrect(fields.incorrect);
+        flags.set_revealed(fields.revealed);
+        flags
+    }
+}
+
+impl From<SquareFlags> for SquareFlagFields {
+    fn from(flags: SquareFlags) -> SquareFlagFields {
+        SquareFlagFields {
+            circled: flags.is_circled(),
+            previously_incorrect: flags.is_previously_incorrect(),
+            incorrect: flags.is_incorrect(),
+            revealed: flags.is_revealed(),
+        }
+    }
+}
+
+/// Per-square flags (circles, incorrect and revealed marks) from the GEXT
+/// extension
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Markup {
+    squares: Vec<SquareFlags>,
+}
+
+impl Markup {
+    pub fn new(cell_count: usize) -> Markup {
+        Markup {
+            squares: vec![SquareFlags::default(); cell_count],
+        }
+    }
+
+    pub fn from_extension(extension: Option<&Extension>, cell_count: usize) -> Result<Markup> {
+        match extension {
+            Some(extension) if extension.bytes.len() != cell_count => Err(Error::msg(format!(
+                "GEXT extension has {} squares but the puzzle has {}",
+                extension.bytes.len(),
+                cell_count
+            ))),
+            Some(extension) => Ok(Markup {
+                squares: extension
+                    .bytes
+                    .iter()
+                    .map(|&bits| SquareFlags::from_bits(bits))
+                    .collect(),
+            }),
+            None => Ok(Markup::new(cell_count)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.squares.iter().all(|flags| flags.bits() == 0)
+    }
+
+    pub fn get(&self, index: usize) -> SquareFlags {
+        self.squares.get(index).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, index: usize, flags: SquareFlags) -> Result<()> {
+        let square = self
+            .squares
+            .get_mut(index)
+            .ok_or_else(|| Error::msg(format!("Square {} is outside of the markup grid", index)))?;
+
+        *square = flags;
+
+        Ok(())
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        self.squares.iter().map(SquareFlags::bits).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SquareFlags;
+
+    #[test]
+    fn test_flags() {
+        let mut flags = SquareFlags::from_bits(0x81);
+        assert!(flags.is_circled());
+        assert!(!flags.is_revealed());
+
+        flags.set_revealed(true);
+        flags.set_circled(false);
+        assert!(flags.is_revealed());
+        assert!(!flags.is_circled());
+
+        // unknown bits are preserved
+        assert_eq!(flags.bits(), 0x41);
+    }
+}
@@ -0,0 +1,112 @@
+use serde::Deserialize;
+
+/// What to do when a checksum stored in a .puz file doesn't match its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumVerification {
+    /// refuse the file
+    Strict,
+    /// load the file, recording each mismatch in `Puzzle::parse_warnings`
+    Warn,
+    /// load the file without mentioning the mismatches
+    Ignore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ParseOptions {
+    #[serde(rename = "verifyChecksums")]
+    pub verify_checksums: ChecksumVerification,
+    /// recalculate the checksums when the puzzle is written. Turning this
+    /// off writes the file's own checksums back, so damaged files round-trip
+    /// byte for byte.
+    #[serde(rename = "repairChecksums")]
+    pub repair_checksums: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            verify_checksums: ChecksumVerification::Strict,
+            repair_checksums: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChecksumVerification, ParseOptions};
+    use crate::Puzzle;
+    use anyhow::Result;
+
+    /// A fixture whose solution and GEXT extension no longer match their
+    /// checksums
+    fn damaged() -> Result<Vec<u8>> {
+        let mut data = std::fs::read("../test_files/nyt_rebus_with_notes_and_shape.puz")?;
+
+        // the first letter of the solution, just after the 52 byte header
+        data[52] ^= 1;
+        // the checksum follows the extension's code and length
+        let gext = data
+            .windows(4)
+            .position(|window| window == b"GEXT")
+            .unwrap();
+        data[gext + 6] ^= 1;
+
+        Ok(data)
+    }
+
+    #[test]
+    fn test_verify_checksums() -> Result<()> {
+        assert!(Puzzle::from_puz(damaged()?).is_err());
+
+        let warn = ParseOptions {
+            verify_checksums: ChecksumVerification::Warn,
+            ..ParseOptions::default()
+        };
+        let puzzle = Puzzle::from_puz_with_options(damaged()?, &warn)?;
+        let warnings = puzzle
+            .parse_warnings()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].starts_with("Calculated global checksum"));
+        assert!(warnings[1].starts_with("Calculated magic checksum"));
+        assert!(warnings[2].starts_with("Calculated GEXT checksum"));
+
+        let ignore = ParseOptions {
+            verify_checksums: ChecksumVerification::Ignore,
+            ..ParseOptions::default()
+        };
+        let puzzle = Puzzle::from_puz_with_options(damaged()?, &ignore)?;
+        assert!(puzzle.parse_warnings().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_checksums() -> Result<()> {
+        let repaired = Puzzle::from_puz_with_options(
+            damaged()?,
+            &ParseOptions {
+                verify_checksums: ChecksumVerification::Ignore,
+                repair_checksums: true,
+            },
+        )?
+        .to_puz()?;
+        assert!(Puzzle::from_puz(repaired)?.parse_warnings().is_empty());
+
+        let preserved = Puzzle::from_puz_with_options(
+            damaged()?,
+            &ParseOptions {
+                verify_checksums: ChecksumVerification::Ignore,
+                repair_checksums: false,
+            },
+        )?
+        .to_puz()?;
+        assert!(preserved == damaged()?);
+
+        Ok(())
+    }
+}
@@ -1,22 +1,38 @@
 use crate::data_checksum::data_checksum;
+use crate::error::{js_error, PuzError};
 use crate::extension::Extension;
 use crate::grid::Grid;
 use crate::header::Header;
+use crate::html::HtmlOptions;
+use crate::markup::{Markup, SquareFlags};
+use crate::parse_options::{ChecksumVerification, ParseOptions};
 use crate::puzzle_buffer::PuzzleBuffer;
+use crate::rebus::{Rebus, RebusFill};
+use crate::scramble::{
+    find_keys, scramble_solution, scrambled_checksum, unscramble_solution, KeyCandidate,
+};
+use crate::solution_state::SolutionState;
+use crate::square::Square;
+use crate::svg::SvgOptions;
+use crate::timer::Timer;
+use crate::validate::{Diagnostic, Severity};
 use crate::Clue;
 use crate::Clues;
 use anyhow::{Context, Error, Result};
 use wasm_bindgen::prelude::*;
 
-const ACROSSDOWN: &'static str = "ACROSS&DOWN";
+const ACROSSDOWN: &str = "ACROSS&DOWN";
+/// where the file version is within the header
+const FILE_VERSION_OFFSET: usize = 0x18;
+/// where the checksum of a locked solution, which the key scrambles, is
+/// within the header
+const SCRAMBLED_CHECKSUM_OFFSET: usize = 0x1E;
 
 /// Represents a crossword puzzle
 #[wasm_bindgen]
 pub struct Puzzle {
-    #[allow(dead_code)]
     pub(crate) preamble: Vec<u8>,
     pub(crate) header: Header,
-    #[allow(dead_code)]
     pub(crate) postscript: Vec<u8>,
     #[wasm_bindgen(getter_with_clone)]
     pub title: String,
@@ -34,9 +50,25 @@ pub struct Puzzle {
     pub(crate) all_clues: Vec<String>,
     #[wasm_bindgen(skip)]
     pub clues: Clues,
-    pub(crate) notes: String,
-    #[allow(dead_code)]
+    #[wasm_bindgen(getter_with_clone)]
+    pub notes: String,
     pub(crate) extensions: Vec<Extension>,
+    #[wasm_bindgen(skip)]
+    pub rebus: Rebus,
+    #[wasm_bindgen(skip)]
+    pub markup: Markup,
+    #[wasm_bindgen(skip)]
+    pub rebus_fill: RebusFill,
+    #[wasm_bindgen(skip)]
+    pub timer: Option<Timer>,
+
+    /// problems tolerated while parsing, per `ParseOptions`
+    pub(crate) parse_warnings: Vec<Diagnostic>,
+    /// write the checksums that were read rather than recalculating them
+    pub(crate) preserve_checksums: bool,
+    /// headers of an xd file that the puzzle has no field for, in the order
+    /// they were read, so `to_xd` can write them back
+    pub(crate) xd_headers: Vec<(String, String)>,
 }
 
 #[wasm_bindgen]
@@ -53,23 +85,29 @@ impl Puzzle {
 
     #[wasm_bindgen(js_name = clues)]
     pub fn clues_js(&self) -> std::result::Result<JsValue, JsValue> {
-        JsValue::from_serde(&self.clues).map_err(|error| {
-            JsValue::from_str(&format!("Failed to convert to JS value: {:?}", error))
+        serde_wasm_bindgen::to_value(&self.clues).map_err(|error| {
+            js_error(Error::msg(format!(
+                "Failed to convert to JS value: {}",
+                error
+            )))
         })
     }
 
     #[wasm_bindgen(js_name = grid)]
     pub fn grid_js(&self) -> std::result::Result<JsValue, JsValue> {
         let grid = Grid::for_puzzle(self);
-        JsValue::from_serde(&grid).map_err(|error| {
-            JsValue::from_str(&format!("Failed to convert to JS value: {:?}", error))
+        serde_wasm_bindgen::to_value(&grid).map_err(|error| {
+            js_error(Error::msg(format!(
+                "Failed to convert to JS value: {}",
+                error
+            )))
         })
     }
 
     #[wasm_bindgen(js_name = getAcrossClue)]
     pub fn get_across_clue_js(&self, row: usize, column: usize) -> JsValue {
         self.get_across_clue(row, column)
-            .map(JsValue::from_serde)
+            .map(serde_wasm_bindgen::to_value)
             .and_then(|res| res.ok())
             .unwrap_or(JsValue::NULL)
     }
@@ -77,24 +115,349 @@ impl Puzzle {
     #[wasm_bindgen(js_name = getDownClue)]
     pub fn get_down_clue_js(&self, row: usize, column: usize) -> JsValue {
         self.get_down_clue(row, column)
-            .map(JsValue::from_serde)
+            .map(serde_wasm_bindgen::to_value)
             .and_then(|res| res.ok())
             .unwrap_or(JsValue::NULL)
     }
 
+    #[wasm_bindgen(js_name = checkAnswer)]
+    pub fn check_answer_js(&self, row: usize, column: usize, answer: &str) -> bool {
+        self.check_answer(row, column, answer)
+    }
+
+    #[wasm_bindgen(js_name = setSquareFlags)]
+    pub fn set_square_flags_js(
+        &mut self,
+        row: usize,
+        column: usize,
+        flags: JsValue,
+    ) -> std::result::Result<(), JsValue> {
+        let flags: SquareFlags = serde_wasm_bindgen::from_value(flags).map_err(|error| {
+            js_error(Error::msg(format!(
+                "Failed to convert from JS value: {}",
+                error
+            )))
+        })?;
+
+        self.set_square_flags(row, column, flags).map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = getRebusFill)]
+    pub fn get_rebus_fill_js(&self, row: usize, column: usize) -> Option<String> {
+        self.get_rebus_fill(row, column).map(String::from)
+    }
+
+    #[wasm_bindgen(js_name = setRebusFill)]
+    pub fn set_rebus_fill_js(
+        &mut self,
+        row: usize,
+        column: usize,
+        entry: &str,
+    ) -> std::result::Result<(), JsValue> {
+        self.set_rebus_fill(row, column, entry).map_err(js_error)
+    }
+
+    #[wasm_bindgen(getter, js_name = timer)]
+    pub fn timer_js(&self) -> std::result::Result<JsValue, JsValue> {
+        match self.timer {
+            Some(timer) => serde_wasm_bindgen::to_value(&timer).map_err(|error| {
+                js_error(Error::msg(format!(
+                    "Failed to convert to JS value: {}",
+                    error
+                )))
+            }),
+            None => Ok(JsValue::NULL),
+        }
+    }
+
+    #[wasm_bindgen(js_name = setTimer)]
+    pub fn set_timer_js(&mut self, elapsed_seconds: u32, running: bool) {
+        self.timer = Some(Timer {
+            elapsed: std::time::Duration::from_secs(elapsed_seconds.into()),
+            running,
+        });
+    }
+
+    #[wasm_bindgen(js_name = unlock)]
+    pub fn unlock_js(&mut self, key: u16) -> std::result::Result<(), JsValue> {
+        self.unlock(key).map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = isFillCorrect)]
+    pub fn is_fill_correct_js(&self) -> bool {
+        self.is_fill_correct()
+    }
+
+    #[wasm_bindgen(js_name = lock)]
+    pub fn lock_js(&mut self, key: u16) -> std::result::Result<(), JsValue> {
+        self.lock(key).map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = findUnlockKey)]
+    pub fn find_unlock_key_js(&self) -> std::result::Result<JsValue, JsValue> {
+        let candidates = self.find_unlock_key().map_err(js_error)?;
+
+        serde_wasm_bindgen::to_value(&candidates).map_err(|error| {
+            js_error(Error::msg(format!(
+                "Failed to convert to JS value: {}",
+                error
+            )))
+        })
+    }
+
     #[wasm_bindgen(getter, js_name = solutionState)]
     pub fn solution_state_js(&self) -> String {
         format!("{:?}", self.header.solution_state)
     }
 
     #[wasm_bindgen(js_name = fromPuz)]
-    pub fn from_puz_js(data: &[u8]) -> std::result::Result<Puzzle, JsValue> {
-        Puzzle::from_puz(data.into()).map_err(|error| JsValue::from_str(&format!("{:?}", error)))
+    pub fn from_puz_js(data: &[u8], options: JsValue) -> std::result::Result<Puzzle, JsValue> {
+        let options: ParseOptions = if options.is_undefined() {
+            ParseOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options).map_err(|error| {
+                js_error(Error::msg(format!(
+                    "Failed to convert from JS value: {}",
+                    error
+                )))
+            })?
+        };
+
+        Ok(Puzzle::from_puz_with_options(data.into(), &options)?)
+    }
+
+    #[wasm_bindgen(getter, js_name = parseWarnings)]
+    pub fn parse_warnings_js(&self) -> std::result::Result<JsValue, JsValue> {
+        let warnings = self
+            .parse_warnings
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        serde_wasm_bindgen::to_value(&warnings).map_err(|error| {
+            js_error(Error::msg(format!(
+                "Failed to convert to JS value: {}",
+                error
+            )))
+        })
+    }
+
+    #[wasm_bindgen(js_name = toPuz)]
+    pub fn to_puz_js(&self) -> std::result::Result<Vec<u8>, JsValue> {
+        self.to_puz().map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = fromIpuz)]
+    pub fn from_ipuz_js(data: &str) -> std::result::Result<Puzzle, JsValue> {
+        Puzzle::from_ipuz(data).map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = toIpuz)]
+    pub fn to_ipuz_js(&self) -> std::result::Result<String, JsValue> {
+        self.to_ipuz().map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = fromJpz)]
+    pub fn from_jpz_js(data: &[u8]) -> std::result::Result<Puzzle, JsValue> {
+        Puzzle::from_jpz(data).map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = toJpz)]
+    pub fn to_jpz_js(&self) -> std::result::Result<String, JsValue> {
+        self.to_jpz().map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = fromAcrossLiteText)]
+    pub fn from_across_lite_text_js(text: &str) -> std::result::Result<Puzzle, JsValue> {
+        Puzzle::from_across_lite_text(text).map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = toAcrossLiteText)]
+    pub fn to_across_lite_text_js(&self) -> std::result::Result<String, JsValue> {
+        self.to_across_lite_text().map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = fromXd)]
+    pub fn from_xd_js(text: &str) -> std::result::Result<Puzzle, JsValue> {
+        Puzzle::from_xd(text).map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = toXd)]
+    pub fn to_xd_js(&self) -> std::result::Result<String, JsValue> {
+        self.to_xd().map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = toExolve)]
+    pub fn to_exolve_js(&self) -> std::result::Result<String, JsValue> {
+        self.to_exolve().map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = toSvg)]
+    pub fn to_svg_js(&self, options: JsValue) -> std::result::Result<String, JsValue> {
+        let options: SvgOptions = if options.is_undefined() {
+            SvgOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options).map_err(|error| {
+                js_error(Error::msg(format!(
+                    "Failed to convert from JS value: {}",
+                    error
+                )))
+            })?
+        };
+
+        self.to_svg(&options).map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = toHtml)]
+    pub fn to_html_js(&self, options: JsValue) -> std::result::Result<String, JsValue> {
+        let options: HtmlOptions = if options.is_undefined() {
+            HtmlOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options).map_err(|error| {
+                js_error(Error::msg(format!(
+                    "Failed to convert from JS value: {}",
+                    error
+                )))
+            })?
+        };
+
+        self.to_html(&options).map_err(js_error)
     }
 }
 
 impl Puzzle {
-    pub fn from_puz(data: Vec<u8>) -> Result<Puzzle> {
+    /// Creates an unlocked puzzle with an empty fill from its solution. The
+    /// clues must be in the order they're stored in .puz files: by number,
+    /// with across before down.
+    pub fn new(
+        width: usize,
+        height: usize,
+        solution: &str,
+        all_clues: Vec<String>,
+    ) -> Result<Puzzle> {
+        if width == 0 || height == 0 || width > 255 || height > 255 {
+            return Err(Error::msg(format!(
+                "A {}x{} grid cannot be stored in a puz file",
+                width, height
+            )));
+        }
+
+        if solution.chars().count() != width * height {
+            return Err(Error::msg(format!(
+                "Expected {} squares in the solution; received {}",
+                width * height,
+                solution.chars().count()
+            )));
+        }
+
+        let fill = solution
+            .chars()
+            .map(|square| {
+                if Square::is_black_square(square) {
+                    square
+                } else {
+                    '-'
+                }
+            })
+            .collect::<String>();
+
+        let clues = Clues::new(Grid::new(&fill, solution, width, height), &all_clues)?;
+        if clues.across.len() + clues.down.len() != all_clues.len() {
+            return Err(Error::msg(format!(
+                "The grid has {} clues but {} were provided",
+                clues.across.len() + clues.down.len(),
+                all_clues.len()
+            )));
+        }
+
+        let cell_count = width * height;
+        let mut puzzle = Self {
+            preamble: vec![],
+            header: Header::new(width, height, all_clues.len()),
+            postscript: vec![],
+            title: String::new(),
+            author: String::new(),
+            copyright: String::new(),
+            fill,
+            solution: solution.to_string(),
+            all_clues,
+            clues,
+            notes: String::new(),
+            extensions: vec![],
+            rebus: Rebus::new(cell_count),
+            markup: Markup::new(cell_count),
+            rebus_fill: RebusFill::new(cell_count),
+            timer: None,
+            parse_warnings: vec![],
+            preserve_checksums: false,
+            xd_headers: vec![],
+        };
+
+        puzzle.upgrade_version_for_text();
+        puzzle.refresh_checksums()?;
+
+        Ok(puzzle)
+    }
+
+    /// Switches to version 2.0 of the format, which is encoded as UTF-8, if
+    /// any of the text can't be encoded as ISO-8859-1
+    pub(crate) fn upgrade_version_for_text(&mut self) {
+        let is_latin1 = |text: &str| text.chars().all(|character| (character as u32) < 0x100);
+
+        let texts_are_latin1 = [&self.title, &self.author, &self.copyright, &self.notes]
+            .iter()
+            .all(|text| is_latin1(text))
+            && self.clue_texts().iter().all(|clue| is_latin1(clue));
+
+        if !texts_are_latin1
+            && self
+                .header
+                .version_tuple()
+                .is_ok_and(|(major, _)| major < 2)
+        {
+            self.header.file_version = "2.0".to_string();
+        }
+    }
+
+    pub fn from_puz(data: Vec<u8>) -> Result<Puzzle, PuzError> {
+        Puzzle::from_puz_with_options(data, &ParseOptions::default())
+    }
+
+    /// Parses a .puz file, refusing it if it's damaged or doesn't have a
+    /// clue for every word. Checksum mismatches are handled according to
+    /// the options; they and any other problems that are tolerated, such as
+    /// more clues than words, can be found in `parse_warnings`.
+    pub fn from_puz_with_options(
+        data: Vec<u8>,
+        options: &ParseOptions,
+    ) -> Result<Puzzle, PuzError> {
+        let mut puz = Puzzle::from_puz_unverified(data)?;
+        puz.preserve_checksums = !options.repair_checksums;
+
+        for diagnostic in puz.validate()? {
+            if diagnostic.severity() != Severity::Error {
+                continue;
+            }
+
+            match (&diagnostic, options.verify_checksums) {
+                (Diagnostic::ChecksumMismatch { .. }, ChecksumVerification::Ignore) => {}
+                (Diagnostic::ChecksumMismatch { .. }, ChecksumVerification::Strict) => {
+                    return Err(PuzError::Invalid(diagnostic))
+                }
+                (Diagnostic::ClueCountMismatch { clues, slots }, _) if clues < slots => {
+                    return Err(PuzError::Invalid(diagnostic))
+                }
+                _ => puz.parse_warnings.push(diagnostic),
+            }
+        }
+
+        Ok(puz)
+    }
+
+    /// Parses a .puz file without verifying it, so damaged files can still
+    /// be inspected with `checksums` and `validate`. Slots beyond the clues
+    /// in the file are given empty clues.
+    pub fn from_puz_unverified(data: Vec<u8>) -> Result<Puzzle, PuzError> {
         let mut buffer = PuzzleBuffer::new(&data);
 
         // advance to start - files may contain some data before the
@@ -103,49 +466,98 @@ impl Puzzle {
         buffer.seek_to(ACROSSDOWN, -2)?;
         let preamble = buffer.seen().to_vec();
 
+        let header_offset = buffer.position();
         let header = buffer.unpack_header()?;
 
-        buffer.set_decoder(header.get_decoder()?);
+        // the version decides the encoding of all of the text
+        let decoder = header
+            .get_decoder()
+            .map_err(|error| PuzError::InvalidField {
+                field: "file version".to_string(),
+                offset: header_offset + FILE_VERSION_OFFSET,
+                message: error.to_string(),
+            })?;
+        buffer.set_decoder(decoder);
 
         let solution = buffer.unpack_solution(header.width, header.height)?;
 
         let fill = buffer.unpack_fill(header.width, header.height)?;
 
-        let title = buffer.unpack_string().context("Failed to parse title")?;
-        let author = buffer.unpack_string().context("Failed to parse author")?;
-        let copyright = buffer
-            .unpack_string()
-            .context("Failed to parse copyright")?;
-
-        let all_clues =
-            (0..header.clue_count).fold(Ok(vec![]), |previous, index| -> Result<Vec<String>> {
-                previous.and_then(|mut clues| {
-                    clues.push(
-                        buffer
-                            .unpack_string()
-                            .context(format!("Failed to parse clue #{}", index))?,
-                    );
-
-                    Ok(clues)
-                })
-            })?;
+        let title = buffer.unpack_string("title")?;
+        let author = buffer.unpack_string("author")?;
+        let copyright = buffer.unpack_string("copyright")?;
+
+        let all_clues = (0..header.clue_count)
+            .map(|index| buffer.unpack_string(&format!("clue #{}", index + 1)))
+            .collect::<Result<Vec<String>, PuzError>>()?;
+
+        let notes = buffer.unpack_string("notes")?;
+
+        let extensions = buffer.unpack_extensions()?;
+
+        let invalid_extension = |code: &str| {
+            let code = code.to_string();
+            move |error: Error| PuzError::InvalidExtension {
+                code,
+                message: format!("{:#}", error),
+            }
+        };
+
+        let rebus = Rebus::from_extensions(
+            Extension::find(&extensions, b"GRBS"),
+            Extension::find(&extensions, b"RTBL"),
+            header.width * header.height,
+            decoder,
+        )
+        .map_err(invalid_extension("GRBS"))?;
+
+        // markup, rebus fill and timers that can't be read are kept as they
+        // were, to be written back
+        let mut parse_warnings = vec![];
+        let unreadable_extension = |code: &str, error: Error| Diagnostic::UnreadableExtension {
+            code: code.to_string(),
+            message: format!("{:#}", error),
+        };
 
-        let notes = buffer.unpack_string().context("Failed to parse notes")?;
+        let markup = Markup::from_extension(
+            Extension::find(&extensions, b"GEXT"),
+            header.width * header.height,
+        )
+        .unwrap_or_else(|error| {
+            parse_warnings.push(unreadable_extension("GEXT", error));
+            Markup::new(header.width * header.height)
+        });
 
-        let extensions = buffer
-            .unpack_extensions()
-            .context("Failed to unpack extensions")?;
+        let rebus_fill = RebusFill::from_extension(
+            Extension::find(&extensions, b"RUSR"),
+            header.width * header.height,
+            decoder,
+        )
+        .unwrap_or_else(|error| {
+            parse_warnings.push(unreadable_extension("RUSR", error));
+            RebusFill::new(header.width * header.height)
+        });
+
+        let timer =
+            Timer::from_extension(Extension::find(&extensions, b"LTIM")).unwrap_or_else(|error| {
+                parse_warnings.push(unreadable_extension("LTIM", error));
+                None
+            });
 
         // sometimes there's some extra garbage at
         // the end of the file, usually \r\n
         let postscript = buffer.upcoming().into();
 
+        // files with too few clues are still read, leaving the last slots
+        // empty, so `validate` can report them
+        let empty = String::new();
         let clues = Clues::new(
             Grid::new(&fill, &solution, header.width, header.height),
-            &all_clues,
-        )?;
+            all_clues.iter().chain(std::iter::repeat(&empty)),
+        )
+        .expect("an endless supply of clues cannot run out");
 
-        let puz = Self {
+        Ok(Self {
             header,
             preamble,
             postscript,
@@ -158,53 +570,144 @@ impl Puzzle {
             clues,
             notes,
             extensions,
+            rebus,
+            markup,
+            rebus_fill,
+            timer,
+            parse_warnings,
+            preserve_checksums: false,
+            xd_headers: vec![],
+        })
+    }
+
+    /// Serializes the puzzle to the .puz format. All checksums are
+    /// recalculated unless the puzzle was parsed with `repair_checksums`
+    /// turned off, and any data surrounding the puzzle in the original file
+    /// is preserved, so an unmodified puzzle round-trips exactly.
+    pub fn to_puz(&self) -> Result<Vec<u8>> {
+        let encode = self.header.get_encoder()?;
+        let encode_zstring = move |string| {
+            encode(string).map(|mut encoded| {
+                encoded.push(b'\0');
+                encoded
+            })
         };
 
-        let calculated_checksum = puz
-            .calculate_global_checksum()
-            .context("Failed to calculate global checksum")?;
-        if calculated_checksum != puz.header.global_checksum {
-            return Err(Error::msg(format!(
-                "Calculated global checksum {} does not match header checksum {}",
-                calculated_checksum, puz.header.global_checksum
-            )));
+        let header = if self.preserve_checksums {
+            self.header.clone()
+        } else {
+            Header {
+                global_checksum: self
+                    .calculate_global_checksum()
+                    .context("Failed to calculate global checksum")?,
+                header_checksum: self
+                    .header
+                    .calculate_checksum()
+                    .context("Failed to calculate header checksum")?,
+                magic_checksum: self
+                    .calculate_magic_checksum()
+                    .context("Failed to calculate magic checksum")?,
+                ..self.header.clone()
+            }
+        };
+
+        let mut data = self.preamble.clone();
+        data.extend(header.pack().context("Failed to pack header")?);
+        data.extend(encode(&self.solution).context("Failed to encode solution")?);
+        data.extend(encode(&self.fill).context("Failed to encode fill")?);
+        data.extend(encode_zstring(&self.title).context("Failed to encode title")?);
+        data.extend(encode_zstring(&self.author).context("Failed to encode author")?);
+        data.extend(encode_zstring(&self.copyright).context("Failed to encode copyright")?);
+
+        for (index, clue) in self.clue_texts().into_iter().enumerate() {
+            data.extend(encode_zstring(clue).context(format!("Failed to encode clue #{}", index))?);
         }
 
-        let calculated_header_checksum = puz
-            .header
-            .calculate_checksum()
-            .context("Failed to calculate header checksum")?;
-        if calculated_header_checksum != puz.header.header_checksum {
-            return Err(Error::msg(format!(
-                "Calculated header checksum {} does not match header checksum {}",
-                calculated_header_checksum, puz.header.header_checksum
-            )));
+        data.extend(encode_zstring(&self.notes).context("Failed to encode notes")?);
+
+        for extension in self.pack_extensions()? {
+            let extension = if self.preserve_checksums {
+                extension
+            } else {
+                Extension::new(extension.code, extension.bytes)
+            };
+            data.extend(extension.pack().context("Failed to pack extension")?);
         }
 
-        let calculated_magic_checksum = puz
-            .calculate_magic_checksum()
-            .context("Failed to calculate magic checksum")?;
-        if calculated_magic_checksum != puz.header.magic_checksum {
-            return Err(Error::msg(format!(
-                "Calculated magic checksum {} does not match header checksum {}",
-                calculated_magic_checksum, puz.header.magic_checksum
-            )));
+        data.extend(&self.postscript);
+
+        Ok(data)
+    }
+
+    /// Returns the extensions with any typed data on the puzzle packed
+    /// back into them
+    fn pack_extensions(&self) -> Result<Vec<Extension>> {
+        let encode = self.header.get_encoder()?;
+        let mut extensions = self.extensions.clone();
+
+        let grid = self.rebus.pack_grid();
+        let table = self.rebus.pack_table(encode)?;
+        if self.rebus.is_empty() {
+            // some files come with an empty rebus, which is kept as it was
+            // read; one that's been emptied is dropped
+            extensions.retain(|extension| match &extension.code {
+                b"GRBS" => extension.bytes == grid,
+                b"RTBL" => extension.bytes == table,
+                _ => true,
+            });
+        } else {
+            Extension::replace(&mut extensions, *b"GRBS", grid, true);
+            Extension::replace(&mut extensions, *b"RTBL", table, true);
         }
 
-        Ok(puz)
+        // an unreadable extension is written back as it was read unless
+        // its data has been set since
+        if !(self.markup.is_empty() && self.is_unreadable_extension("GEXT")) {
+            Extension::replace(
+                &mut extensions,
+                *b"GEXT",
+                self.markup.pack(),
+                !self.markup.is_empty(),
+            );
+        }
+
+        if !(self.rebus_fill.is_empty() && self.is_unreadable_extension("RUSR")) {
+            Extension::replace(
+                &mut extensions,
+                *b"RUSR",
+                self.rebus_fill.pack(encode)?,
+                !self.rebus_fill.is_empty(),
+            );
+        }
+
+        match self.timer {
+            Some(timer) => Extension::replace(&mut extensions, *b"LTIM", timer.pack(), true),
+            None if self.is_unreadable_extension("LTIM") => {}
+            None => extensions.retain(|extension| &extension.code != b"LTIM"),
+        }
+
+        Ok(extensions)
+    }
+
+    /// Whether the extension with this code was kept as raw bytes because
+    /// it couldn't be read
+    fn is_unreadable_extension(&self, code: &str) -> bool {
+        self.parse_warnings.iter().any(|warning| {
+            matches!(warning, Diagnostic::UnreadableExtension { code: unreadable, .. } if unreadable == code)
+        })
     }
 
-    fn calculate_global_checksum(&self) -> Result<u16> {
+    pub(crate) fn calculate_global_checksum(&self) -> Result<u16> {
         let encode = self.header.get_encoder()?;
-        let mut checksum = self.header.header_checksum;
+        let mut checksum = self.header.calculate_checksum()?;
         checksum = data_checksum(&encode(&self.solution)?, checksum);
         checksum = data_checksum(&encode(&self.fill)?, checksum);
         self.text_checksum(checksum)
     }
 
-    fn calculate_magic_checksum(&self) -> Result<u64> {
+    pub(crate) fn calculate_magic_checksum(&self) -> Result<u64> {
         let encode = self.header.get_encoder()?;
-        const MASK_STRING: &'static str = "ICHEATED";
+        const MASK_STRING: &str = "ICHEATED";
         let magic_checksum =
             [
                 self.header.calculate_checksum()?,
@@ -235,43 +738,103 @@ impl Puzzle {
         Ok(magic_checksum)
     }
 
+    /// The clues as they're written to a .puz file: the text of each clue in
+    /// `clues`, in puz order, followed by any extra clues the file had beyond
+    /// the words in its grid
+    fn clue_texts(&self) -> Vec<&str> {
+        let mut texts = self
+            .clues
+            .in_puz_order()
+            .map(|(_, clue)| clue.text.as_str())
+            .chain(
+                self.all_clues
+                    .iter()
+                    .skip(self.clues.across.len() + self.clues.down.len())
+                    .map(String::as_str),
+            )
+            .collect::<Vec<_>>();
+        // a file with too few clues keeps only the ones it had
+        texts.truncate(self.all_clues.len());
+        texts
+    }
+
     fn text_checksum(&self, mut checksum: u16) -> Result<u16> {
         let encode = self.header.get_encoder()?;
         let encode_zstring = move |string| {
             encode(string).map(|mut encoded| {
-                encoded.push('\0' as u8);
+                encoded.push(b'\0');
                 encoded
             })
         };
         // for the checksum to work these fields must be added in order with
         // null termination, followed by all non-empty clues without null
         // termination, followed by notes (but only for version >= 1.3)
-        if self.title.len() > 0 {
+        if !self.title.is_empty() {
             checksum = data_checksum(&encode_zstring(&self.title)?, checksum);
         }
-        if self.author.len() > 0 {
+        if !self.author.is_empty() {
             checksum = data_checksum(&encode_zstring(&self.author)?, checksum);
         }
-        if self.copyright.len() > 0 {
+        if !self.copyright.is_empty() {
             checksum = data_checksum(&encode_zstring(&self.copyright)?, checksum);
         }
 
         checksum = self
-            .all_clues
-            .iter()
-            .fold(Ok(checksum), |sum: Result<u16>, clue| {
-                Ok(data_checksum(&encode(clue)?, sum?))
+            .clue_texts()
+            .into_iter()
+            .try_fold(checksum, |sum, clue| -> Result<u16> {
+                Ok(data_checksum(&encode(clue)?, sum))
             })?;
 
         let (major, minor) = self.header.version_tuple()?;
         // notes included in global checksum starting v1.3 of format
-        if major > 1 || major == 1 && minor >= 3 && self.notes.len() > 0 {
+        if (major > 1 || major == 1 && minor >= 3) && !self.notes.is_empty() {
             checksum = data_checksum(&encode_zstring(&self.notes)?, checksum)
         }
 
         Ok(checksum)
     }
 
+    /// Whether the solution is scrambled (locked) or not
+    pub fn solution_state(&self) -> SolutionState {
+        self.header.solution_state
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.header.solution_state == SolutionState::Locked
+    }
+
+    /// Fails for locked puzzles, whose scrambled solution would otherwise
+    /// be written or shown as if it were the answer. `action` finishes the
+    /// sentence "Locked puzzles must be unlocked before ..."
+    pub(crate) fn ensure_unlocked(&self, action: &str) -> Result<()> {
+        if self.is_locked() {
+            return Err(Error::msg(format!(
+                "Locked puzzles must be unlocked before {}",
+                action
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Problems that were tolerated when the puzzle was parsed, such as
+    /// extra clues or checksum mismatches with `ChecksumVerification::Warn`
+    pub fn parse_warnings(&self) -> &[Diagnostic] {
+        &self.parse_warnings
+    }
+
+    /// The puzzle's header. Its checksums are the ones read from the file;
+    /// `checksums` compares them with the puzzle's contents.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The extension sections of the file, in file order
+    pub fn extensions(&self) -> &[Extension] {
+        &self.extensions
+    }
+
     pub fn get_across_clue(&self, row: usize, column: usize) -> Option<&Clue> {
         self.clues.across.iter().find(|clue| {
             let column_range = clue.column..(clue.column + clue.length);
@@ -282,25 +845,219 @@ impl Puzzle {
     pub fn get_down_clue(&self, row: usize, column: usize) -> Option<&Clue> {
         self.clues.down.iter().find(|clue| {
             let row_range = clue.row..(clue.row + clue.length);
-            if clue.column == 2 {
-                println!(
-                    "{} Checking {} against range {:?}",
-                    clue.text, row, row_range
-                );
-            }
             clue.column == column && row_range.contains(&row)
         })
     }
 
+    /// Unscrambles the solution of a locked puzzle using its 4 digit key.
+    /// The key is checked against the scrambled checksum in the header, and
+    /// the puzzle is left untouched if it is wrong.
+    pub fn unlock(&mut self, key: u16) -> Result<()> {
+        if self.header.solution_state != SolutionState::Locked {
+            return Err(Error::msg("Puzzle is not locked"));
+        }
+
+        let solution = unscramble_solution(&self.solution, self.width(), self.height(), key)
+            .context("Failed to unscramble solution")?;
+
+        if scrambled_checksum(&solution, self.width(), self.height())
+            != self.header.scrambled_checksum
+        {
+            return Err(Error::msg(format!("{} is not the key to this puzzle", key)));
+        }
+
+        self.solution = solution;
+        self.header.solution_state = SolutionState::Unlocked;
+        self.header.scrambled_checksum = 0;
+
+        self.refresh_checksums()
+    }
+
+    /// Scrambles the solution with a 4 digit key so it can be distributed
+    /// without revealing the answers, as Across Lite does
+    pub fn lock(&mut self, key: u16) -> Result<()> {
+        if self.header.solution_state == SolutionState::Locked {
+            return Err(Error::msg("Puzzle is already locked"));
+        }
+        // Across Lite only accepts keys with 4 significant digits
+        if !(1000..=9999).contains(&key) {
+            return Err(PuzError::InvalidField {
+                field: "key".to_string(),
+                offset: self.preamble.len() + SCRAMBLED_CHECKSUM_OFFSET,
+                message: format!("{} is not between 1000 and 9999", key),
+            }
+            .into());
+        }
+
+        let solution = scramble_solution(&self.solution, self.width(), self.height(), key)
+            .context("Failed to scramble solution")?;
+
+        self.header.scrambled_checksum =
+            scrambled_checksum(&self.solution, self.width(), self.height());
+        self.solution = solution;
+        self.header.solution_state = SolutionState::Locked;
+
+        self.refresh_checksums()
+    }
+
+    /// Recalculates the checksums stored in the header after the puzzle
+    /// has changed
+    pub(crate) fn refresh_checksums(&mut self) -> Result<()> {
+        self.header.header_checksum = self
+            .header
+            .calculate_checksum()
+            .context("Failed to calculate header checksum")?;
+        self.header.global_checksum = self
+            .calculate_global_checksum()
+            .context("Failed to calculate global checksum")?;
+        self.header.magic_checksum = self
+            .calculate_magic_checksum()
+            .context("Failed to calculate magic checksum")?;
+
+        Ok(())
+    }
+
+    /// Searches every key from 1000 to 9999 for one that unlocks the puzzle.
+    /// Several keys can match the scrambled checksum, so all of them are
+    /// returned, ranked by how much the unscrambled letters look like
+    /// English.
+    pub fn find_unlock_key(&self) -> Result<Vec<KeyCandidate>> {
+        if self.header.solution_state != SolutionState::Locked {
+            return Err(Error::msg("Puzzle is not locked"));
+        }
+
+        find_keys(
+            &self.solution,
+            self.width(),
+            self.height(),
+            self.header.scrambled_checksum,
+        )
+    }
+
+    /// Checks whether the fill is complete and correct. Locked puzzles are
+    /// checked against the scrambled checksum, so this works without the key
+    /// and without revealing the solution, though as a 16 bit checksum it can
+    /// very rarely accept a wrong fill.
+    pub fn is_fill_correct(&self) -> bool {
+        let squares_match = self
+            .fill
+            .chars()
+            .zip(self.solution.chars())
+            .all(|(fill, solution)| {
+                Square::is_black_square(fill) == Square::is_black_square(solution)
+            });
+
+        if !squares_match || self.fill.chars().count() != self.solution.chars().count() {
+            return false;
+        }
+
+        match self.header.solution_state {
+            SolutionState::Locked => {
+                self.fill
+                    .chars()
+                    .all(|square| Square::is_black_square(square) || square.is_ascii_uppercase())
+                    && scrambled_checksum(&self.fill, self.width(), self.height())
+                        == self.header.scrambled_checksum
+            }
+            SolutionState::Unlocked => {
+                let grid = self.grid();
+                self.fill.chars().enumerate().all(|(index, square)| {
+                    Square::is_black_square(square)
+                        || grid.check_answer(
+                            index,
+                            self.rebus_fill.get(index).unwrap_or(&square.to_string()),
+                        )
+                })
+            }
+        }
+    }
+
+    /// Checks an answer for the square at the given position against the
+    /// solution, taking rebus squares into account
+    pub fn check_answer(&self, row: usize, column: usize, answer: &str) -> bool {
+        row < self.height()
+            && column < self.width()
+            && self
+                .grid()
+                .check_answer(row * self.width() + column, answer)
+    }
+
+    /// Sets the flags (circled, revealed etc.) of the square at the given
+    /// position, to be written to the GEXT extension
+    pub fn set_square_flags(
+        &mut self,
+        row: usize,
+        column: usize,
+        flags: SquareFlags,
+    ) -> Result<()> {
+        if row >= self.height() || column >= self.width() {
+            return Err(Error::msg(format!(
+                "Square ({}, {}) is outside of the grid",
+                row, column
+            )));
+        }
+
+        let index = row * self.width() + column;
+        self.markup.set(index, flags)
+    }
+
+    /// Returns the solver's multi-character entry at the given position,
+    /// if any
+    pub fn get_rebus_fill(&self, row: usize, column: usize) -> Option<&str> {
+        if row >= self.height() || column >= self.width() {
+            return None;
+        }
+
+        self.rebus_fill.get(row * self.width() + column)
+    }
+
+    /// Sets the solver's multi-character entry at the given position. The
+    /// fill keeps the first character of the entry, as Across Lite does,
+    /// and an empty entry clears the square.
+    pub fn set_rebus_fill(&mut self, row: usize, column: usize, entry: &str) -> Result<()> {
+        if row >= self.height() || column >= self.width() {
+            return Err(Error::msg(format!(
+                "Square ({}, {}) is outside of the grid",
+                row, column
+            )));
+        }
+
+        let index = row * self.width() + column;
+        let fill_character = entry.chars().next().unwrap_or('-');
+        self.fill = self
+            .fill
+            .chars()
+            .enumerate()
+            .map(|(fill_index, character)| {
+                if fill_index == index {
+                    fill_character
+                } else {
+                    character
+                }
+            })
+            .collect();
+
+        if entry.chars().count() > 1 {
+            self.rebus_fill.set(index, entry)
+        } else {
+            self.rebus_fill.clear(index);
+            Ok(())
+        }
+    }
+
     pub fn grid<'a>(&'a self) -> Grid<'a> {
-        Grid::for_puzzle(&self)
+        Grid::for_puzzle(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Puzzle, PuzzleType, SolutionState};
+    use crate::extension::Extension;
+    use crate::test_fixtures::read_fixture;
+    use crate::validate::Diagnostic;
+    use crate::{HtmlOptions, PuzError, Puzzle, PuzzleType, SolutionState, SquareFlags, Timer};
     use anyhow::Result;
+    use std::time::Duration;
 
     #[test]
     fn test_header_parsing() {
@@ -324,7 +1081,10 @@ mod tests {
         let bytes = std::fs::read("../test_files/washpost.puz").unwrap();
         let puzzle = Puzzle::from_puz(bytes).unwrap();
 
-        assert_eq!(puzzle.solution, "");
+        assert_eq!(
+            puzzle.solution,
+            "LAMB.SPAT.CARVEORAL.ALEE.OBIESFIRESTORM.QUOTETAKEASWIPEAT......PIC.ETTU.SPFPHD.DOS..AVATARRUES.ROOK.IRANIESCAPEATTENTIONSHAKE.POEM.SNUGTUNERS..LBJ.STEOPT.FOAM.EEL......HOPEANDFAITHMAJOR.GUIDEPOSTAXIOM.IDLE.SWATTEMPS.SEED.EARP"
+        );
     }
 
     #[test]
@@ -332,7 +1092,10 @@ mod tests {
         let bytes = std::fs::read("../test_files/washpost.puz").unwrap();
         let puzzle = Puzzle::from_puz(bytes).unwrap();
 
-        assert_eq!(puzzle.fill, "");
+        assert_eq!(
+            puzzle.fill,
+            "----.----.---------.----.--------------.-----------------......---.----.------.---..----------.----.-------------------------.----.----------..---.------.----.---......-----------------.--------------.----.---------.----.----"
+        );
     }
 
     #[test]
@@ -347,6 +1110,800 @@ mod tests {
 
         let clue = puzzle.get_across_clue(0, 5).unwrap();
         assert_eq!(clue.clue_number, 5);
+        assert_eq!(clue.length, 4);
+
+        // black square between 1-Across and 5-Across
+        assert!(puzzle.get_across_clue(0, 4).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let bytes = std::fs::read("../test_files/washpost.puz")?;
+        let puzzle = Puzzle::from_puz(bytes.clone())?;
+
+        assert_eq!(puzzle.to_puz()?, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_after_edit() -> Result<()> {
+        let bytes = std::fs::read("../test_files/washpost.puz")?;
+        let mut puzzle = Puzzle::from_puz(bytes)?;
+
+        puzzle.title = "A new title".to_string();
+        puzzle.fill = puzzle.solution.clone();
+
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert_eq!(reparsed.title, "A new title");
+        assert_eq!(reparsed.fill, puzzle.solution);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_out_of_range_header() -> Result<()> {
+        let bytes = std::fs::read("../test_files/washpost.puz")?;
+        let mut puzzle = Puzzle::from_puz(bytes)?;
+
+        puzzle.header.clue_count = 0x10000;
+        assert!(puzzle.to_puz().is_err());
+
+        puzzle.header.clue_count = puzzle.all_clues.len();
+        puzzle.header.width = 256;
+        assert!(puzzle.to_puz().is_err());
+        assert!(puzzle.header.pack().is_err());
+
+        Ok(())
+    }
+
+    const FIXTURES: [&str; 15] = [
+        "Feb0308_oddnumbering.puz",
+        "av110622.puz",
+        "cs080904.puz",
+        "nyt_diagramless.puz",
+        "nyt_locked.puz",
+        "nyt_partlyfilled.puz",
+        "nyt_rebus_with_notes_and_shape.puz",
+        "nyt_sun_rebus.puz",
+        "nyt_v1_4.puz",
+        "nyt_weekday_with_notes.puz",
+        "nyt_with_shape.puz",
+        "unicode.puz",
+        "washpost.puz",
+        "wsj110624.puz",
+        "zack.puz",
+    ];
+
+    fn extension_codes(puzzle: &Puzzle) -> Vec<&str> {
+        puzzle
+            .extensions
+            .iter()
+            .map(|extension| std::str::from_utf8(&extension.code).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip_all_fixtures() -> Result<()> {
+        for name in FIXTURES.iter() {
+            let bytes = std::fs::read(format!("../test_files/{}", name))?;
+            let puzzle = Puzzle::from_puz(bytes.clone())?;
+
+            assert!(puzzle.to_puz()? == bytes, "{} did not round-trip", name);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_clue_count_all_fixtures() -> Result<()> {
+        for name in FIXTURES.iter() {
+            let puzzle = read_fixture(name)?;
+
+            assert_eq!(
+                puzzle.clues.across.len() + puzzle.clues.down.len(),
+                puzzle.header.clue_count,
+                "{} has a clue count mismatch",
+                name
+            );
+            assert_eq!(puzzle.all_clues.len(), puzzle.header.clue_count);
+            assert_eq!(
+                puzzle.solution.chars().count(),
+                puzzle.width() * puzzle.height()
+            );
+            assert_eq!(
+                puzzle.fill.chars().count(),
+                puzzle.width() * puzzle.height()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preamble() -> Result<()> {
+        let puzzle = read_fixture("washpost.puz")?;
+        assert_eq!(puzzle.preamble.len(), 13);
+        assert!(puzzle.postscript.is_empty());
+
+        let puzzle = read_fixture("nyt_locked.puz")?;
+        assert!(puzzle.preamble.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_postscript() -> Result<()> {
+        // bytes after the last extension that can't be another one
+        for name in ["washpost.puz", "nyt_rebus_with_notes_and_shape.puz"] {
+            let bytes = std::fs::read(format!("../test_files/{}", name))?;
+            for postscript in [&b"\r\n"[..], b"abc", b"\r\nsome trailing garbage"] {
+                let mut data = bytes.clone();
+                data.extend_from_slice(postscript);
+
+                let puzzle = Puzzle::from_puz(data.clone())?;
+                assert_eq!(puzzle.postscript, postscript);
+                assert_eq!(puzzle.to_puz()?, data);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_edited_clues() -> Result<()> {
+        let mut puzzle = read_fixture("zack.puz")?;
+        puzzle.clues.across[1].text = "Edited across".to_string();
+        puzzle.clues.down[0].text = "Edited down".to_string();
+        puzzle.notes = "Edited notes".to_string();
+
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert_eq!(reparsed.clues.across[1].text, "Edited across");
+        assert_eq!(reparsed.clues.down[0].text, "Edited down");
+        // 5 Across and 1 Down, in puz order
+        assert_eq!(reparsed.all_clues[5], "Edited across");
+        assert_eq!(reparsed.all_clues[1], "Edited down");
+        assert_eq!(reparsed.notes, "Edited notes");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crossynergy() -> Result<()> {
+        let puzzle = read_fixture("cs080904.puz")?;
+
+        assert_eq!(puzzle.header.file_version, "1.2");
+        assert_eq!(puzzle.title, "September 4, 2008 - \"CD Collection\"");
+        assert_eq!(puzzle.author, "By Mel Rosen");
+        assert_eq!(
+            puzzle.copyright,
+            "© 2008 Mel Rosen. Distributed by CrosSynergy(TM) Syndicate"
+        );
+        assert_eq!((puzzle.width(), puzzle.height()), (15, 15));
+        assert_eq!(puzzle.all_clues.len(), 78);
+        assert_eq!(
+            puzzle.clues.across[0].text,
+            "Nocturnal ungulate with a long snout"
+        );
+        assert_eq!(puzzle.clues.down[0].text, "Unspoken, as an approval");
+        assert!(puzzle
+            .solution
+            .starts_with("TAPIR.LOOK.ACEDAMEBA.APSE.MARECOSMICDUST"));
+        assert!(puzzle.fill.starts_with("-----.----.---------.----.-----"));
+        assert_eq!(puzzle.notes, "");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wall_street_journal() -> Result<()> {
+        let puzzle = read_fixture("wsj110624.puz")?;
+
+        assert_eq!(puzzle.header.file_version, "1.3");
+        assert_eq!(puzzle.title, "June 24, 2011 - Good News, Bad News");
+        // the surrounding spaces are in the file
+        assert_eq!(puzzle.author, "  by Randolph Ross / Edited by Mike Shenk  ");
+        assert_eq!(puzzle.copyright, "© 2011 Wall Street Journal");
+        assert_eq!((puzzle.width(), puzzle.height()), (21, 21));
+        assert_eq!(puzzle.all_clues.len(), 138);
+        assert_eq!(puzzle.clues.across[0].text, "Iditarod sight");
+        assert_eq!(
+            puzzle.clues.down[0].text,
+            "Croatian area known for a breed of dog"
+        );
+        assert_eq!(puzzle.all_clues.last().unwrap(), "Had a life");
+        assert!(puzzle
+            .solution
+            .starts_with("DOGSLED.AGUILERA.AGOGABALONE.VAMPIRES.ME"));
+        assert!(puzzle.fill.starts_with("-------.--------.-----------.---"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_v1_2_with_garbage_version_byte() -> Result<()> {
+        let puzzle = read_fixture("av110622.puz")?;
+
+        assert_eq!(puzzle.header.file_version, "1.2");
+        assert_eq!(puzzle.header.file_version_trailer, b'c');
+        assert_eq!(puzzle.title, "AV Club xword, 6 22 11");
+        assert_eq!(puzzle.author, "Ben Tausig");
+        assert_eq!(puzzle.copyright, "");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_v1_4() -> Result<()> {
+        let puzzle = read_fixture("nyt_v1_4.puz")?;
+
+        assert_eq!(puzzle.header.file_version, "1.4");
+        assert_eq!(puzzle.width(), 21);
+        assert_eq!(puzzle.height(), 21);
+        assert_eq!(puzzle.header.clue_count, 140);
+        assert!(puzzle
+            .notes
+            .starts_with("Drop one letter from each set of circled letters in the grid"));
+        assert_eq!(extension_codes(&puzzle), vec!["GRBS", "GEXT"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_locked() -> Result<()> {
+        let puzzle = read_fixture("nyt_locked.puz")?;
+
+        assert_eq!(puzzle.header.solution_state, SolutionState::Locked);
+        assert_eq!(puzzle.header.scrambled_checksum, 40861);
+        assert_eq!(puzzle.title, "NY Times, Fri, Sep 19, 2008");
+        assert_eq!(puzzle.author, "Alex Boisvert / Will Shortz");
+        assert!(puzzle
+            .solution
+            .starts_with("BDIBWGUECUC.SNYDLFXAILCOLU.WTX"));
+        assert!(puzzle.fill.starts_with("-----------.--------------.---"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagramless() -> Result<()> {
+        let puzzle = read_fixture("nyt_diagramless.puz")?;
+
+        assert_eq!(puzzle.header.puzzle_type, PuzzleType::Diagramless);
+        assert_eq!(puzzle.header.solution_state, SolutionState::Locked);
+        assert_eq!(puzzle.width(), 17);
+        assert_eq!(puzzle.height(), 17);
+        assert!(puzzle.fill.starts_with("::::::-----:::::::::::-------:"));
+        assert!(puzzle
+            .notes
+            .starts_with("This diagramless is 17 squares wide by 17 squares deep"));
+        assert_eq!(extension_codes(&puzzle), vec!["LTIM"]);
+
+        assert_eq!(puzzle.clues.across[0].clue_number, 1);
+        assert_eq!(puzzle.clues.across[0].text, "Panorama");
+        assert_eq!(puzzle.clues.across[0].column, 6);
+        assert_eq!(puzzle.clues.down.last().unwrap().clue_number, 69);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unicode() -> Result<()> {
+        let puzzle = read_fixture("unicode.puz")?;
+
+        assert_eq!(puzzle.header.file_version, "2.0");
+        assert_eq!(puzzle.title, "⚔️");
+        assert_eq!(puzzle.author, "Chris Pickel");
+        assert_eq!(puzzle.copyright, "2018, Chris Pickel, under MIT License");
+        assert_eq!(puzzle.solution, "SPAOHMLIT");
+        assert_eq!(puzzle.clues.across[0].text, "💆🏻\u{200d}♂️");
+        // OHM SIGN, not GREEK CAPITAL LETTER OMEGA
+        assert_eq!(puzzle.clues.across[1].text, "\u{2126}");
+        assert_eq!(puzzle.clues.down[2].text, "量");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_notes() -> Result<()> {
+        let puzzle = read_fixture("nyt_weekday_with_notes.puz")?;
+
+        assert!(puzzle.notes.starts_with("TEEN PUZZLEMAKER WEEK\r\n"));
+        assert_eq!(puzzle.header.clue_count, 70);
+
+        let puzzle = read_fixture("nyt_rebus_with_notes_and_shape.puz")?;
+        assert!(puzzle.notes.starts_with("TEEN PUZZLEMAKER WEEK\r\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebus_and_shape_extensions() -> Result<()> {
+        let puzzle = read_fixture("nyt_rebus_with_notes_and_shape.puz")?;
+        assert_eq!(
+            extension_codes(&puzzle),
+            vec!["GRBS", "RTBL", "LTIM", "GEXT"]
+        );
+
+        let puzzle = read_fixture("nyt_sun_rebus.puz")?;
+        assert_eq!(extension_codes(&puzzle), vec!["GRBS", "RTBL", "LTIM"]);
+
+        let puzzle = read_fixture("nyt_with_shape.puz")?;
+        assert_eq!(extension_codes(&puzzle), vec!["GRBS", "GEXT"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_partly_filled() -> Result<()> {
+        let puzzle = read_fixture("nyt_partlyfilled.puz")?;
+
+        assert!(puzzle.fill.starts_with("FLAG.----.-----EARL.----.-----"));
+        assert!(puzzle
+            .solution
+            .starts_with("FLAG.FOCI.SALUTEARL.RAIN.ALONE"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_odd_numbering() -> Result<()> {
+        let puzzle = read_fixture("Feb0308_oddnumbering.puz")?;
+
+        assert_eq!(puzzle.clues.across.len(), 67);
+        assert_eq!(puzzle.clues.down.len(), 73);
+
+        let numbers = puzzle
+            .clues
+            .across
+            .iter()
+            .take(4)
+            .map(|clue| (clue.clue_number, clue.row, clue.column, clue.length))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            numbers,
+            vec![(1, 0, 1, 4), (5, 0, 6, 6), (11, 0, 14, 7), (18, 1, 0, 5)]
+        );
+
+        let last_down = puzzle.clues.down.last().unwrap();
+        assert_eq!(last_down.clue_number, 107);
+        assert_eq!(last_down.text, "Great ball of fire");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mini() -> Result<()> {
+        let puzzle = read_fixture("zack.puz")?;
+
+        assert_eq!(puzzle.title, "");
+        assert_eq!(puzzle.solution, "XKCD.RURALAMALEYATES.REKT");
+        assert_eq!(puzzle.clues.across[0].length, 4);
+        assert_eq!(puzzle.clues.down[4].clue_number, 6);
+        assert_eq!(puzzle.clues.down[4].row, 1);
+        assert_eq!(puzzle.clues.down[4].length, 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebus() -> Result<()> {
+        let puzzle = read_fixture("nyt_rebus_with_notes_and_shape.puz")?;
+
+        assert!(!puzzle.rebus.is_empty());
+        for &index in [22, 112, 202].iter() {
+            assert_eq!(puzzle.rebus.get(index), Some("STAR"));
+            assert_eq!(puzzle.grid().get_solution(index), Some("STAR".to_string()));
+        }
+        assert_eq!(puzzle.rebus.get(0), None);
+        assert_eq!(puzzle.grid().get_solution(0), Some("G".to_string()));
+
+        assert!(puzzle.check_answer(1, 7, "STAR"));
+        assert!(puzzle.check_answer(1, 7, "S"));
+        assert!(!puzzle.check_answer(1, 7, "ST"));
+        assert!(puzzle.check_answer(0, 0, "G"));
+        assert!(!puzzle.check_answer(0, 0, "GREW"));
+        assert!(!puzzle.check_answer(15, 0, "G"));
+
+        let puzzle = read_fixture("nyt_sun_rebus.puz")?;
+        assert_eq!(puzzle.rebus.get(52), Some("FEB"));
+        assert_eq!(puzzle.rebus.get(69), Some("JAN"));
+
+        let puzzle = read_fixture("nyt_with_shape.puz")?;
+        assert!(puzzle.rebus.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_rebus() -> Result<()> {
+        let mut puzzle = read_fixture("washpost.puz")?;
+        assert!(puzzle.rebus.is_empty());
+
+        puzzle.rebus.set(0, "LAMB")?;
+
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert_eq!(extension_codes(&reparsed), vec!["GRBS", "RTBL"]);
+        assert_eq!(reparsed.rebus.get(0), Some("LAMB"));
+        assert!(reparsed.check_answer(0, 0, "LAMB"));
+
+        // clearing every rebus square drops both extensions rather than
+        // writing an empty grid and an unused table
+        let mut puzzle = read_fixture("nyt_rebus_with_notes_and_shape.puz")?;
+        let squares = (0..puzzle.width() * puzzle.height())
+            .filter(|&index| puzzle.rebus.get(index).is_some())
+            .collect::<Vec<_>>();
+        assert!(squares.len() > 1);
+        for &index in &squares {
+            puzzle.rebus.clear(index);
+        }
+
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert!(reparsed.rebus.is_empty());
+        assert!(!extension_codes(&reparsed)
+            .iter()
+            .any(|&code| code == "GRBS" || code == "RTBL"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_markup() -> Result<()> {
+        let puzzle = read_fixture("nyt_with_shape.puz")?;
+
+        let circled = (0..puzzle.width() * puzzle.height())
+            .filter(|&index| puzzle.grid().get_flags(index).is_circled())
+            .collect::<Vec<_>>();
+        assert_eq!(circled.len(), 34);
+        assert_eq!(circled[..5], [48, 51, 52, 53, 54]);
+        assert!(!puzzle.grid().get_flags(0).is_revealed());
+
+        let puzzle = read_fixture("washpost.puz")?;
+        assert!(puzzle.markup.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_markup() -> Result<()> {
+        let mut puzzle = read_fixture("nyt_with_shape.puz")?;
+
+        let mut flags = puzzle.grid().get_flags(48);
+        flags.set_revealed(true);
+        flags.set_incorrect(true);
+        puzzle.set_square_flags(2, 6, flags)?;
+        assert!(puzzle.set_square_flags(21, 0, flags).is_err());
+
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert_eq!(extension_codes(&reparsed), vec!["GRBS", "GEXT"]);
+        let flags = reparsed.grid().get_flags(48);
+        assert!(flags.is_circled());
+        assert!(flags.is_revealed());
+        assert!(flags.is_incorrect());
+        assert!(!flags.is_previously_incorrect());
+
+        let mut puzzle = read_fixture("washpost.puz")?;
+        let mut flags = SquareFlags::default();
+        flags.set_circled(true);
+        puzzle.set_square_flags(0, 0, flags)?;
+
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert_eq!(extension_codes(&reparsed), vec!["GEXT"]);
+        assert!(reparsed.grid().get_flags(0).is_circled());
+
+        Ok(())
+    }
+
+    /// washpost.puz, which has no extensions, followed by one extension
+    fn washpost_with_extension(code: [u8; 4], bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut data = std::fs::read("../test_files/washpost.puz")?;
+        data.extend(Extension::new(code, bytes.to_vec()).pack()?);
+        Ok(data)
+    }
+
+    #[test]
+    fn test_unreadable_markup() -> Result<()> {
+        // three squares rather than one for each cell
+        let data = washpost_with_extension(*b"GEXT", b"\x80\x80\x80")?;
+        let mut puzzle = Puzzle::from_puz(data.clone())?;
+        assert!(puzzle.markup.is_empty());
+        assert!(matches!(
+            puzzle.parse_warnings(),
+            [Diagnostic::UnreadableExtension { code, .. }] if code == "GEXT"
+        ));
+        assert_eq!(puzzle.to_puz()?, data);
+
+        let mut flags = SquareFlags::default();
+        flags.set_circled(true);
+        puzzle.set_square_flags(0, 0, flags)?;
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert!(reparsed.parse_warnings().is_empty());
+        assert!(reparsed.grid().get_flags(0).is_circled());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_rebus_fill() -> Result<()> {
+        // the entries aren't null terminated
+        let data = washpost_with_extension(*b"RUSR", b"STAR")?;
+        let mut puzzle = Puzzle::from_puz(data.clone())?;
+        assert!(puzzle.rebus_fill.is_empty());
+        assert!(matches!(
+            puzzle.parse_warnings(),
+            [Diagnostic::UnreadableExtension { code, .. }] if code == "RUSR"
+        ));
+        assert_eq!(puzzle.to_puz()?, data);
+
+        puzzle.set_rebus_fill(0, 0, "STAR")?;
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert!(reparsed.parse_warnings().is_empty());
+        assert_eq!(reparsed.get_rebus_fill(0, 0), Some("STAR"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_timer() -> Result<()> {
+        let data = washpost_with_extension(*b"LTIM", b"abc,0")?;
+        let mut puzzle = Puzzle::from_puz(data.clone())?;
+        assert_eq!(puzzle.timer, None);
+        assert!(matches!(
+            puzzle.parse_warnings(),
+            [Diagnostic::UnreadableExtension { code, .. }] if code == "LTIM"
+        ));
+        assert_eq!(puzzle.to_puz()?, data);
+
+        puzzle.timer = Some(Timer {
+            elapsed: Duration::from_secs(30),
+            running: false,
+        });
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert!(reparsed.parse_warnings().is_empty());
+        assert_eq!(reparsed.timer, puzzle.timer);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_rebus_fill() -> Result<()> {
+        let mut puzzle = read_fixture("nyt_rebus_with_notes_and_shape.puz")?;
+        assert!(puzzle.rebus_fill.is_empty());
+
+        puzzle.set_rebus_fill(1, 7, "STAR")?;
+        puzzle.set_rebus_fill(0, 0, "G")?;
+        assert!(puzzle.set_rebus_fill(15, 0, "STAR").is_err());
+        assert_eq!(puzzle.fill.chars().nth(22), Some('S'));
+
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert_eq!(
+            extension_codes(&reparsed),
+            vec!["GRBS", "RTBL", "LTIM", "GEXT", "RUSR"]
+        );
+        assert_eq!(reparsed.get_rebus_fill(1, 7), Some("STAR"));
+        assert_eq!(reparsed.get_rebus_fill(0, 0), None);
+        assert_eq!(reparsed.fill.chars().next(), Some('G'));
+        assert_eq!(reparsed.fill.chars().nth(22), Some('S'));
+
+        let mut puzzle = reparsed;
+        puzzle.set_rebus_fill(1, 7, "")?;
+        assert_eq!(puzzle.get_rebus_fill(1, 7), None);
+        assert_eq!(puzzle.fill.chars().nth(22), Some('-'));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_timer() -> Result<()> {
+        let puzzle = read_fixture("nyt_partlyfilled.puz")?;
+        assert_eq!(
+            puzzle.timer,
+            Some(Timer {
+                elapsed: Duration::from_secs(8),
+                running: true
+            })
+        );
+
+        let puzzle = read_fixture("nyt_rebus_with_notes_and_shape.puz")?;
+        assert_eq!(
+            puzzle.timer,
+            Some(Timer {
+                elapsed: Duration::from_secs(0),
+                running: false
+            })
+        );
+
+        let puzzle = read_fixture("washpost.puz")?;
+        assert_eq!(puzzle.timer, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_timer() -> Result<()> {
+        let mut puzzle = read_fixture("nyt_partlyfilled.puz")?;
+        puzzle.timer = Some(Timer {
+            elapsed: Duration::from_secs(754),
+            running: true,
+        });
+
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert_eq!(extension_codes(&reparsed), vec!["LTIM"]);
+        assert_eq!(reparsed.timer, puzzle.timer);
+
+        let mut puzzle = reparsed;
+        puzzle.timer = None;
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert!(reparsed.extensions.is_empty());
+        assert_eq!(reparsed.timer, None);
+
+        let mut puzzle = read_fixture("washpost.puz")?;
+        puzzle.timer = Some(Timer::default());
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert_eq!(reparsed.timer, Some(Timer::default()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unlock() -> Result<()> {
+        let mut puzzle = read_fixture("nyt_locked.puz")?;
+        let scrambled = puzzle.solution.clone();
+
+        assert!(puzzle.unlock(1234).is_err());
+        assert_eq!(puzzle.solution, scrambled);
+        assert_eq!(puzzle.header.solution_state, SolutionState::Locked);
+
+        let mut shortened = read_fixture("nyt_locked.puz")?;
+        shortened.solution.truncate(10);
+        assert!(shortened.unlock(7844).is_err());
+
+        puzzle.unlock(7844)?;
+        assert_eq!(puzzle.header.solution_state, SolutionState::Unlocked);
+        assert_eq!(puzzle.header.scrambled_checksum, 0);
+        assert!(puzzle
+            .solution
+            .starts_with("PANDORASBOX.SONIMOUTOFHERE.ANE"));
+        assert!(puzzle.unlock(7844).is_err());
+
+        let reparsed = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert_eq!(reparsed.header.solution_state, SolutionState::Unlocked);
+        assert_eq!(reparsed.solution, puzzle.solution);
+
+        let mut puzzle = read_fixture("nyt_diagramless.puz")?;
+        puzzle.unlock(3285)?;
+        assert!(puzzle
+            .solution
+            .starts_with("::::::VISTA:::::::::::PENCILS:"));
+
+        let mut puzzle = read_fixture("washpost.puz")?;
+        assert!(puzzle.unlock(1234).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_unlock_key() -> Result<()> {
+        let puzzle = read_fixture("nyt_locked.puz")?;
+        assert_eq!(puzzle.find_unlock_key()?[0].key, 7844);
+
+        let puzzle = read_fixture("nyt_diagramless.puz")?;
+        let keys = puzzle
+            .find_unlock_key()?
+            .iter()
+            .map(|candidate| candidate.key)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec![3285, 7230]);
+
+        let puzzle = read_fixture("washpost.puz")?;
+        assert!(puzzle.find_unlock_key().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_locked_puzzles_are_not_written() -> Result<()> {
+        // writers would otherwise give the scrambled solution as the answers
+        let locked = read_fixture("nyt_locked.puz")?;
+        assert!(locked.is_locked());
+        assert!(locked.to_ipuz().is_err());
+        assert!(locked.to_jpz().is_err());
+        assert!(locked.to_across_lite_text().is_err());
+        assert!(locked.to_xd().is_err());
+        assert!(locked.to_exolve().is_err());
+        assert!(locked.to_html(&HtmlOptions { answer_key: true }).is_err());
+
+        // puz files store the scrambled solution as it is
+        assert!(locked.to_puz().is_ok());
+        assert!(locked.to_html(&HtmlOptions::default()).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock() -> Result<()> {
+        let mut puzzle = read_fixture("washpost.puz")?;
+        let solution = puzzle.solution.clone();
+
+        puzzle.lock(4321)?;
+        assert_eq!(puzzle.header.solution_state, SolutionState::Locked);
+        assert_ne!(puzzle.solution, solution);
+        assert_ne!(puzzle.header.scrambled_checksum, 0);
+        assert!(puzzle.lock(4321).is_err());
+
+        for key in [0, 999, 10000] {
+            let mut unlocked = read_fixture("washpost.puz")?;
+            let error = unlocked.lock(key).unwrap_err().downcast::<PuzError>()?;
+            assert!(matches!(error, PuzError::InvalidField { ref field, .. } if field == "key"));
+            assert_eq!(unlocked.solution, solution);
+        }
+
+        let mut shortened = read_fixture("washpost.puz")?;
+        shortened.solution.pop();
+        assert!(shortened.lock(4321).is_err());
+        assert_eq!(shortened.header.solution_state, SolutionState::Unlocked);
+
+        // the checksums in the header are kept up to date
+        let bytes = puzzle.to_puz()?;
+        let mut reparsed = Puzzle::from_puz(bytes.clone())?;
+        assert_eq!(reparsed.to_puz()?, bytes);
+        assert_eq!(
+            reparsed.header.global_checksum,
+            puzzle.header.global_checksum
+        );
+        assert_eq!(reparsed.header.magic_checksum, puzzle.header.magic_checksum);
+
+        assert!(reparsed.unlock(1234).is_err());
+        reparsed.unlock(4321)?;
+        assert_eq!(reparsed.solution, solution);
+        assert_eq!(
+            reparsed.to_puz()?,
+            std::fs::read("../test_files/washpost.puz")?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relock_fixture() -> Result<()> {
+        let bytes = std::fs::read("../test_files/nyt_locked.puz")?;
+        let mut puzzle = Puzzle::from_puz(bytes.clone())?;
+
+        puzzle.unlock(7844)?;
+        puzzle.lock(7844)?;
+        assert_eq!(puzzle.to_puz()?, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_fill_correct() -> Result<()> {
+        let mut puzzle = read_fixture("nyt_locked.puz")?;
+        assert!(!puzzle.is_fill_correct());
+
+        let mut unlocked = read_fixture("nyt_locked.puz")?;
+        unlocked.unlock(7844)?;
+        puzzle.fill = unlocked.solution.clone();
+        assert!(puzzle.is_fill_correct());
+        assert_eq!(puzzle.header.solution_state, SolutionState::Locked);
+
+        puzzle.fill = puzzle.fill.replacen('P', "Q", 1);
+        assert!(!puzzle.is_fill_correct());
+
+        let mut puzzle = read_fixture("washpost.puz")?;
+        assert!(!puzzle.is_fill_correct());
+        puzzle.fill = puzzle.solution.clone();
+        assert!(puzzle.is_fill_correct());
+
+        let mut puzzle = read_fixture("nyt_rebus_with_notes_and_shape.puz")?;
+        puzzle.fill = puzzle.solution.clone();
+        assert!(puzzle.is_fill_correct());
+        puzzle.set_rebus_fill(1, 7, "STAR")?;
+        assert!(puzzle.is_fill_correct());
+        puzzle.set_rebus_fill(1, 7, "SUN")?;
+        assert!(!puzzle.is_fill_correct());
 
         Ok(())
     }
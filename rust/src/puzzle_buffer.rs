@@ -1,22 +1,60 @@
+use crate::error::PuzError;
 use crate::extension::Extension;
-use crate::header::Header;
-use anyhow::{Context, Result};
-use std::io::Cursor;
-use thiserror::Error;
+use crate::header::{Decoder, Header};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Read};
+
+/// Reads a little-endian u16, reporting the field if the data runs out
+pub(crate) fn read_u16<T: AsRef<[u8]>>(
+    reader: &mut Cursor<T>,
+    field: &str,
+) -> Result<u16, PuzError> {
+    let offset = reader.position() as usize;
+    reader
+        .read_u16::<LittleEndian>()
+        .map_err(|_e| PuzError::Truncated {
+            field: field.to_string(),
+            offset,
+        })
+}
+
+/// Reads a little-endian u64, reporting the field if the data runs out
+pub(crate) fn read_u64<T: AsRef<[u8]>>(
+    reader: &mut Cursor<T>,
+    field: &str,
+) -> Result<u64, PuzError> {
+    let offset = reader.position() as usize;
+    reader
+        .read_u64::<LittleEndian>()
+        .map_err(|_e| PuzError::Truncated {
+            field: field.to_string(),
+            offset,
+        })
+}
+
+/// Reads exactly `length` bytes, reporting the field if the data runs out
+pub(crate) fn read_bytes<T: AsRef<[u8]>>(
+    reader: &mut Cursor<T>,
+    length: usize,
+    field: &str,
+) -> Result<Vec<u8>, PuzError> {
+    let offset = reader.position() as usize;
+    let mut bytes = vec![0u8; length];
+    reader
+        .read_exact(&mut bytes)
+        .map_err(|_e| PuzError::Truncated {
+            field: field.to_string(),
+            offset,
+        })?;
+
+    Ok(bytes)
+}
 
 /// Wraps a data buffer
 pub struct PuzzleBuffer<'a> {
     data: &'a [u8],
     cursor: Cursor<&'a [u8]>,
-    decoder: fn(&[u8]) -> Result<String>,
-}
-
-#[derive(Error, Debug)]
-pub enum PuzzleBufferError {
-    #[error("Cannot find '{0}' in data")]
-    SeekError(String),
-    #[error("Encoding has not been inferred")]
-    EncodingNotInferred,
+    decoder: Decoder,
 }
 
 impl<'a> PuzzleBuffer<'a> {
@@ -24,20 +62,25 @@ impl<'a> PuzzleBuffer<'a> {
         PuzzleBuffer {
             data,
             cursor: Cursor::new(data),
-            decoder: |_bytes| Err(PuzzleBufferError::EncodingNotInferred.into()),
+            decoder: |_bytes| Err(PuzError::EncodingNotInferred.into()),
         }
     }
 
-    pub fn set_decoder(&mut self, decoder: fn(&[u8]) -> Result<String>) {
+    pub fn set_decoder(&mut self, decoder: Decoder) {
         self.decoder = decoder;
     }
 
-    fn decode_string(&self, bytes: &[u8]) -> Result<String> {
+    /// Decodes the bytes of a field that started at the given offset
+    fn decode_string(&self, bytes: &[u8], field: &str, offset: usize) -> Result<String, PuzError> {
         let decoder = self.decoder;
-        decoder(bytes)
+        decoder(bytes).map_err(|error| PuzError::Decode {
+            field: field.to_string(),
+            offset,
+            message: error.to_string(),
+        })
     }
 
-    fn position(&self) -> usize {
+    pub fn position(&self) -> usize {
         self.cursor.position() as usize
     }
 
@@ -51,59 +94,71 @@ impl<'a> PuzzleBuffer<'a> {
         &self.data[self.position()..]
     }
 
-    pub fn seek_to(&mut self, substring: &str, offset: i32) -> Result<()> {
+    pub fn seek_to(&mut self, substring: &str, offset: i32) -> Result<(), PuzError> {
         // Finds the index of a "substring" within the buffer in order
         // to set the cursor's position to that substring.
         // This is a naiive port of python's list index function.
         // Presumably there's a better way to do it.
 
-        for (index, window) in self.data[self.position()..]
-            .windows(substring.len())
-            .enumerate()
-        {
+        let start = self.position();
+        for (index, window) in self.data[start..].windows(substring.len()).enumerate() {
             if window == substring.as_bytes() {
+                let found = start + index;
+                // there must be room for the offset before the substring
+                let position = found.checked_add_signed(offset as isize).ok_or_else(|| {
+                    PuzError::InvalidField {
+                        field: substring.to_string(),
+                        offset: found,
+                        message: format!("expected {} bytes before it", -offset),
+                    }
+                })?;
+
                 // set the cursor
-                self.cursor.set_position((index as i32 + offset) as u64);
+                self.cursor.set_position(position as u64);
                 return Ok(());
             }
         }
 
-        Err(PuzzleBufferError::SeekError(substring.to_string()))?
+        Err(PuzError::SeekError(substring.to_string()))
     }
 
-    pub fn unpack_header(&mut self) -> Result<Header> {
+    pub fn unpack_header(&mut self) -> Result<Header, PuzError> {
         Header::from_cursor(&mut self.cursor)
     }
 
-    pub fn unpack_solution(&mut self, width: usize, height: usize) -> Result<String> {
-        use std::io::Read;
-        let mut solution = vec![0u8; width * height];
-        self.cursor.read_exact(&mut solution)?;
+    pub fn unpack_solution(&mut self, width: usize, height: usize) -> Result<String, PuzError> {
+        let offset = self.position();
+        let solution = read_bytes(&mut self.cursor, width * height, "solution")?;
 
-        self.decode_string(&solution)
+        self.decode_string(&solution, "solution", offset)
     }
 
-    pub fn unpack_fill(&mut self, width: usize, height: usize) -> Result<String> {
-        use std::io::Read;
-        let mut fill = vec![0u8; width * height];
-        self.cursor.read_exact(&mut fill)?;
+    pub fn unpack_fill(&mut self, width: usize, height: usize) -> Result<String, PuzError> {
+        let offset = self.position();
+        let fill = read_bytes(&mut self.cursor, width * height, "fill")?;
 
-        self.decode_string(&fill)
+        self.decode_string(&fill, "fill", offset)
     }
 
-    pub fn unpack_string(&mut self) -> Result<String> {
+    /// Reads a null-terminated string, such as the title or a clue
+    pub fn unpack_string(&mut self, field: &str) -> Result<String, PuzError> {
         use std::io::BufRead;
+        let offset = self.position();
         let mut buf = vec![];
-        self.cursor
-            .read_until('\0' as u8, &mut buf)
-            .context(format!("Failed to find null-terminated string"))?;
-
-        buf.pop();
+        // reading from a slice can't fail
+        let _ = self.cursor.read_until(b'\0', &mut buf);
+
+        if buf.pop() != Some(b'\0') {
+            return Err(PuzError::Truncated {
+                field: field.to_string(),
+                offset: self.position(),
+            });
+        }
 
-        self.decode_string(&buf)
+        self.decode_string(&buf, field, offset)
     }
 
-    pub fn unpack_extensions(&mut self) -> Result<Vec<Extension>> {
+    pub fn unpack_extensions(&mut self) -> Result<Vec<Extension>, PuzError> {
         Extension::parse_extensions_from_cursor(&mut self.cursor)
     }
 }
@@ -115,7 +170,7 @@ mod tests {
     #[test]
     fn test_seek_to() {
         let data = "Hello there".as_bytes();
-        let mut buffer = PuzzleBuffer::new(&data);
+        let mut buffer = PuzzleBuffer::new(data);
 
         assert!(buffer.seek_to("there", 2).is_ok());
         assert_eq!(buffer.position(), "Hello ".len() + 2);
@@ -18,9 +18,9 @@ impl TryFrom<u16> for PuzzleType {
     }
 }
 
-impl Into<u16> for PuzzleType {
-    fn into(self) -> u16 {
-        match self {
+impl From<PuzzleType> for u16 {
+    fn from(puzzle_type: PuzzleType) -> u16 {
+        match puzzle_type {
             PuzzleType::Normal => 0x0001,
             PuzzleType::Diagramless => 0x0401,
         }
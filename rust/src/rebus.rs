@@ -0,0 +1,298 @@
+use crate::extension::Extension;
+use crate::header::{Decoder, Encoder};
+use anyhow::{Context, Error, Result};
+use std::collections::BTreeMap;
+
+/// Multi-character solutions for rebus squares, stored in the GRBS
+/// (grid) and RTBL (table) extensions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rebus {
+    /// for each square, 0 if it isn't a rebus or the rebus table key + 1
+    grid: Vec<u8>,
+    /// rebus table key to solution
+    table: BTreeMap<u8, String>,
+}
+
+impl Rebus {
+    pub fn new(cell_count: usize) -> Rebus {
+        Rebus {
+            grid: vec![0; cell_count],
+            table: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_extensions(
+        grid: Option<&Extension>,
+        table: Option<&Extension>,
+        cell_count: usize,
+        decode: Decoder,
+    ) -> Result<Rebus> {
+        let grid = match grid {
+            Some(extension) if extension.bytes.len() != cell_count => {
+                return Err(Error::msg(format!(
+                    "GRBS extension has {} squares but the puzzle has {}",
+                    extension.bytes.len(),
+                    cell_count
+                )))
+            }
+            Some(extension) => extension.bytes.clone(),
+            None => vec![0; cell_count],
+        };
+
+        let table = match table {
+            Some(extension) => Self::parse_table(
+                &decode(&extension.bytes).context("Failed to decode RTBL extension")?,
+            )?,
+            None => BTreeMap::new(),
+        };
+
+        Ok(Rebus { grid, table })
+    }
+
+    /// parses the rebus table, which looks like " 1:HEART; 2:CLUB;"
+    fn parse_table(table: &str) -> Result<BTreeMap<u8, String>> {
+        table
+            .split(';')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| {
+                let (key, solution) = entry.split_once(':').ok_or_else(|| {
+                    Error::msg(format!("Malformed rebus table entry {:?}", entry))
+                })?;
+
+                let key = key
+                    .trim()
+                    .parse::<u8>()
+                    .context(format!("Received non-integer rebus table key: {}", key))?;
+
+                Ok((key, solution.to_string()))
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.grid.iter().all(|&square| square == 0)
+    }
+
+    /// Returns the rebus solution at the given square, if any
+    pub fn get(&self, index: usize) -> Option<&str> {
+        match self.grid.get(index) {
+            Some(&square) if square > 0 => self.table.get(&(square - 1)).map(String::as_str),
+            _ => None,
+        }
+    }
+
+    /// Sets the rebus solution of a square, reusing a table entry when
+    /// another square already has the same solution
+    pub fn set(&mut self, index: usize, solution: &str) -> Result<()> {
+        if index >= self.grid.len() {
+            return Err(Error::msg(format!(
+                "Square {} is outside of the rebus grid",
+                index
+            )));
+        }
+
+        let existing = self
+            .table
+            .iter()
+            .find(|(_key, existing)| existing.as_str() == solution)
+            .map(|(&key, _solution)| key);
+
+        let key = match existing {
+            Some(key) => key,
+            None => {
+                let key = (0..u8::MAX)
+                    .find(|key| !self.table.contains_key(key))
+                    .ok_or_else(|| Error::msg("Rebus table is full"))?;
+                self.table.insert(key, solution.to_string());
+                key
+            }
+        };
+
+        self.grid[index] = key + 1;
+        self.prune_table();
+
+        Ok(())
+    }
+
+    /// Removes the rebus solution of a square
+    pub fn clear(&mut self, index: usize) {
+        if let Some(square) = self.grid.get_mut(index) {
+            *square = 0;
+        }
+        self.prune_table();
+    }
+
+    /// Drops the table entries no square uses any more
+    fn prune_table(&mut self) {
+        let grid = &self.grid;
+        self.table
+            .retain(|key, _solution| grid.contains(&(key + 1)));
+    }
+
+    pub fn pack_grid(&self) -> Vec<u8> {
+        self.grid.clone()
+    }
+
+    pub fn pack_table(&self, encode: Encoder) -> Result<Vec<u8>> {
+        let table = self
+            .table
+            .iter()
+            .map(|(key, solution)| format!("{:2}:{};", key, solution))
+            .collect::<String>();
+
+        encode(&table).context("Failed to encode RTBL extension")
+    }
+}
+
+/// The solver's multi-character entries in rebus squares, stored in the
+/// RUSR extension as a null-terminated string per square
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RebusFill {
+    squares: Vec<String>,
+}
+
+impl RebusFill {
+    pub fn new(cell_count: usize) -> RebusFill {
+        RebusFill {
+            squares: vec![String::new(); cell_count],
+        }
+    }
+
+    pub fn from_extension(
+        extension: Option<&Extension>,
+        cell_count: usize,
+        decode: Decoder,
+    ) -> Result<RebusFill> {
+        let extension = match extension {
+            Some(extension) => extension,
+            None => return Ok(RebusFill::new(cell_count)),
+        };
+
+        let squares = match extension.bytes.split_last() {
+            Some((0, bytes)) => bytes
+                .split(|&byte| byte == 0)
+                .map(|bytes| decode(bytes).context("Failed to decode RUSR extension"))
+                .collect::<Result<Vec<String>>>()?,
+            _ => return Err(Error::msg("RUSR extension is not null-terminated")),
+        };
+
+        if squares.len() != cell_count {
+            return Err(Error::msg(format!(
+                "RUSR extension has {} squares but the puzzle has {}",
+                squares.len(),
+                cell_count
+            )));
+        }
+
+        Ok(RebusFill { squares })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.squares.iter().all(String::is_empty)
+    }
+
+    /// Returns the solver's rebus entry at the given square, if any
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.squares
+            .get(index)
+            .filter(|entry| !entry.is_empty())
+            .map(String::as_str)
+    }
+
+    pub fn set(&mut self, index: usize, entry: &str) -> Result<()> {
+        let square = self.squares.get_mut(index).ok_or_else(|| {
+            Error::msg(format!(
+                "Square {} is outside of the rebus fill grid",
+                index
+            ))
+        })?;
+
+        *square = entry.to_string();
+
+        Ok(())
+    }
+
+    pub fn clear(&mut self, index: usize) {
+        if let Some(square) = self.squares.get_mut(index) {
+            square.clear();
+        }
+    }
+
+    pub fn pack(&self, encode: Encoder) -> Result<Vec<u8>> {
+        self.squares.iter().try_fold(vec![], |mut bytes, entry| {
+            bytes.extend(encode(entry).context("Failed to encode RUSR extension")?);
+            bytes.push(b'\0');
+            Ok(bytes)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rebus, RebusFill};
+    use anyhow::Result;
+
+    #[test]
+    fn test_parse_table() -> Result<()> {
+        let table = Rebus::parse_table(" 1:HEART; 2:CLUB;13:DEC;")?;
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[&1], "HEART");
+        assert_eq!(table[&2], "CLUB");
+        assert_eq!(table[&13], "DEC");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set() -> Result<()> {
+        let mut rebus = Rebus::new(4);
+        assert!(rebus.is_empty());
+
+        rebus.set(1, "HEART")?;
+        rebus.set(3, "HEART")?;
+        rebus.set(2, "CLUB")?;
+
+        assert_eq!(rebus.get(0), None);
+        assert_eq!(rebus.get(1), Some("HEART"));
+        assert_eq!(rebus.get(2), Some("CLUB"));
+        assert_eq!(rebus.get(3), Some("HEART"));
+        assert_eq!(rebus.table.len(), 2);
+
+        // HEART is still used by square 3
+        rebus.clear(1);
+        rebus.clear(2);
+        assert_eq!(rebus.table.len(), 1);
+        assert_eq!(rebus.get(3), Some("HEART"));
+
+        rebus.set(3, "SPADE")?;
+        assert_eq!(rebus.table.len(), 1);
+
+        rebus.clear(3);
+        assert!(rebus.is_empty());
+        assert!(rebus.table.is_empty());
+
+        assert!(rebus.set(4, "SPADE").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebus_fill() -> Result<()> {
+        let mut fill = RebusFill::new(3);
+        assert!(fill.is_empty());
+
+        fill.set(1, "HEART")?;
+        assert_eq!(fill.get(0), None);
+        assert_eq!(fill.get(1), Some("HEART"));
+        assert!(fill.set(3, "CLUB").is_err());
+
+        let encode = |string: &str| Ok(string.as_bytes().to_vec());
+        assert_eq!(fill.pack(encode)?, b"\0HEART\0\0");
+
+        fill.clear(1);
+        assert!(fill.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+//! Across Lite's solution scrambling. Letters are read out of the grid
+//! column by column, skipping black squares, then for each digit of the
+//! 4 digit key the letters are shifted by the key, rotated by the digit
+//! and riffle shuffled.
+use crate::data_checksum::data_checksum;
+use crate::square::Square;
+use anyhow::{Error, Result};
+use serde::Serialize;
+
+const ALPHABET_LENGTH: i32 = 26;
+
+/// Approximate frequency (in percent) of each letter in English text, used
+/// to rank how plausible unscrambled letters look
+const LETTER_FREQUENCIES: [f64; 26] = [
+    8.2, 1.5, 2.8, 4.3, 12.7, 2.2, 2.0, 6.1, 7.0, 0.15, 0.77, 4.0, 2.4, 6.7, 7.5, 1.9, 0.095, 6.0,
+    6.3, 9.1, 2.8, 0.98, 2.4, 0.15, 2.0, 0.074,
+];
+
+/// A key whose unscrambled solution matches the scrambled checksum
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct KeyCandidate {
+    pub key: u16,
+    /// average log frequency of the unscrambled letters; higher is more
+    /// likely to be the real key
+    pub score: f64,
+}
+
+fn key_digits(key: u16) -> Result<[i32; 4]> {
+    if key > 9999 {
+        return Err(Error::msg(format!("{} is not a 4 digit key", key)));
+    }
+
+    let key = key as i32;
+    Ok([key / 1000, key / 100 % 10, key / 10 % 10, key % 10])
+}
+
+/// The solution is indexed by row and column, so it must have a square
+/// for every cell of the grid
+fn check_grid_size(solution: &str, width: usize, height: usize) -> Result<()> {
+    let squares = solution.chars().count();
+    if squares != width * height {
+        return Err(Error::msg(format!(
+            "Expected {} squares in the solution; received {}",
+            width * height,
+            squares
+        )));
+    }
+
+    Ok(())
+}
+
+/// Reads the letters of the grid column by column, skipping black squares
+fn column_major_letters(solution: &str, width: usize, height: usize) -> Vec<char> {
+    let squares = solution.chars().collect::<Vec<_>>();
+
+    (0..width)
+        .flat_map(|column| (0..height).map(move |row| row * width + column))
+        .filter_map(|index| squares.get(index).copied())
+        .filter(|&square| !Square::is_black_square(square))
+        .collect()
+}
+
+/// Puts letters read by `column_major_letters` back into the grid
+fn restore_letters(solution: &str, width: usize, height: usize, letters: &[char]) -> String {
+    let mut squares = solution.chars().collect::<Vec<_>>();
+    let mut letters = letters.iter();
+
+    for column in 0..width {
+        for row in 0..height {
+            let square = &mut squares[row * width + column];
+            if !Square::is_black_square(*square) {
+                *square = *letters
+                    .next()
+                    .expect("as many letters as non-black squares");
+            }
+        }
+    }
+
+    squares.into_iter().collect()
+}
+
+fn shift(letters: &[char], key: &[i32; 4], direction: i32) -> Result<Vec<char>> {
+    letters
+        .iter()
+        .enumerate()
+        .map(|(index, &letter)| {
+            if !letter.is_ascii_uppercase() {
+                return Err(Error::msg(format!(
+                    "Only A-Z can be scrambled; received {:?}",
+                    letter
+                )));
+            }
+
+            let offset = letter as i32 - 'A' as i32 + direction * key[index % key.len()];
+            Ok((offset.rem_euclid(ALPHABET_LENGTH) as u8 + b'A') as char)
+        })
+        .collect()
+}
+
+/// Interleaves the second half of the letters with the first half
+fn shuffle(letters: &[char]) -> Vec<char> {
+    let middle = letters.len() / 2;
+    let mut shuffled = letters[middle..]
+        .iter()
+        .zip(&letters[..middle])
+        .flat_map(|(&second, &first)| vec![second, first])
+        .collect::<Vec<_>>();
+
+    if letters.len() % 2 == 1 {
+        shuffled.push(letters[letters.len() - 1]);
+    }
+
+    shuffled
+}
+
+fn unshuffle(letters: &[char]) -> Vec<char> {
+    letters
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .chain(letters.iter().step_by(2))
+        .copied()
+        .collect()
+}
+
+/// Letters are cut around each key digit, which does nothing when there
+/// are no more letters than the digit
+fn rotation(letters: &[char], digit: i32) -> usize {
+    let digit = digit as usize;
+    if digit < letters.len() {
+        digit
+    } else {
+        0
+    }
+}
+
+pub(crate) fn scramble_letters(letters: &[char], key: u16) -> Result<Vec<char>> {
+    let key = key_digits(key)?;
+    let mut letters = letters.to_vec();
+
+    for &digit in key.iter() {
+        letters = shift(&letters, &key, 1)?;
+        let rotation = rotation(&letters, digit);
+        letters.rotate_left(rotation);
+        letters = shuffle(&letters);
+    }
+
+    Ok(letters)
+}
+
+pub(crate) fn unscramble_letters(letters: &[char], key: u16) -> Result<Vec<char>> {
+    let key = key_digits(key)?;
+    let mut letters = letters.to_vec();
+
+    for &digit in key.iter().rev() {
+        letters = unshuffle(&letters);
+        let rotation = rotation(&letters, digit);
+        letters.rotate_right(rotation);
+        letters = shift(&letters, &key, -1)?;
+    }
+
+    Ok(letters)
+}
+
+pub fn scramble_solution(solution: &str, width: usize, height: usize, key: u16) -> Result<String> {
+    check_grid_size(solution, width, height)?;
+    let letters = column_major_letters(solution, width, height);
+    let scrambled = scramble_letters(&letters, key)?;
+
+    Ok(restore_letters(solution, width, height, &scrambled))
+}
+
+pub fn unscramble_solution(
+    solution: &str,
+    width: usize,
+    height: usize,
+    key: u16,
+) -> Result<String> {
+    check_grid_size(solution, width, height)?;
+    let letters = column_major_letters(solution, width, height);
+    let unscrambled = unscramble_letters(&letters, key)?;
+
+    Ok(restore_letters(solution, width, height, &unscrambled))
+}
+
+/// The checksum Across Lite stores for a locked puzzle, calculated over the
+/// unscrambled letters in column-major order
+pub fn scrambled_checksum(solution: &str, width: usize, height: usize) -> u16 {
+    letters_checksum(&column_major_letters(solution, width, height))
+}
+
+fn letters_checksum(letters: &[char]) -> u16 {
+    let bytes = letters
+        .iter()
+        .map(|&letter| letter as u8)
+        .collect::<Vec<_>>();
+
+    data_checksum(&bytes, 0)
+}
+
+fn plausibility(letters: &[char]) -> f64 {
+    let total = letters
+        .iter()
+        .map(|&letter| (LETTER_FREQUENCIES[(letter as u8 - b'A') as usize] / 100.0).ln())
+        .sum::<f64>();
+
+    total / letters.len().max(1) as f64
+}
+
+/// Tries every key from 1000 to 9999, returning those whose unscrambled
+/// letters match the scrambled checksum, most plausible first
+pub fn find_keys(
+    solution: &str,
+    width: usize,
+    height: usize,
+    checksum: u16,
+) -> Result<Vec<KeyCandidate>> {
+    check_grid_size(solution, width, height)?;
+    let letters = column_major_letters(solution, width, height);
+    let mut candidates = vec![];
+
+    for key in 1000..=9999 {
+        let unscrambled = unscramble_letters(&letters, key)?;
+
+        if letters_checksum(&unscrambled) == checksum {
+            candidates.push(KeyCandidate {
+                key,
+                score: plausibility(&unscrambled),
+            });
+        }
+    }
+
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{plausibility, scramble_solution, shuffle, unscramble_solution, unshuffle};
+    use anyhow::Result;
+
+    #[test]
+    fn test_shuffle() {
+        let letters = "ABCDEFG".chars().collect::<Vec<_>>();
+        let shuffled = shuffle(&letters);
+
+        assert_eq!(shuffled.iter().collect::<String>(), "DAEBFCG");
+        assert_eq!(unshuffle(&shuffled), letters);
+    }
+
+    #[test]
+    fn test_scramble_round_trip() -> Result<()> {
+        let solution = "XKCD.RURALAMALEYATES.REKT";
+        let scrambled = scramble_solution(solution, 5, 5, 1234)?;
+
+        assert_ne!(scrambled, solution);
+        assert_eq!(scrambled.chars().nth(4), Some('.'));
+        assert_eq!(unscramble_solution(&scrambled, 5, 5, 1234)?, solution);
+
+        assert!(scramble_solution(solution, 5, 5, 12345).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unscramble() -> Result<()> {
+        assert_eq!(
+            unscramble_solution("XKCD.RURALAMALEYATES.REKT", 5, 5, 1234)?
+                .chars()
+                .nth(4),
+            Some('.')
+        );
+
+        assert!(unscramble_solution("XKCD.", 5, 1, 12345).is_err());
+        assert!(unscramble_solution("XKC4.", 5, 1, 1234).is_err());
+        assert!(unscramble_solution("XKCD", 5, 1, 1234).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plausibility() {
+        let english = "PANDORASBOX".chars().collect::<Vec<_>>();
+        let garbage = "YJSBEUTVNVZ".chars().collect::<Vec<_>>();
+
+        assert!(plausibility(&english) > plausibility(&garbage));
+    }
+}
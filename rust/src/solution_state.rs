@@ -20,9 +20,9 @@ impl TryFrom<u16> for SolutionState {
     }
 }
 
-impl Into<u16> for SolutionState {
-    fn into(self) -> u16 {
-        match self {
+impl From<SolutionState> for u16 {
+    fn from(solution_state: SolutionState) -> u16 {
+        match solution_state {
             SolutionState::Unlocked => 0x0000,
             SolutionState::Locked => 0x0004,
         }
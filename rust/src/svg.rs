@@ -0,0 +1,238 @@
+use crate::clues::Clues;
+use crate::grid::Grid;
+use crate::square::Square;
+use crate::xml::escape;
+use crate::Puzzle;
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Which letters, if any, are drawn in the squares
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Letters {
+    /// a blank grid, for printing
+    None,
+    /// the solver's entries
+    Fill,
+    Solution,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct SvgOptions {
+    pub letters: Letters,
+    /// width and height of each square, in pixels
+    #[serde(rename = "squareSize")]
+    pub square_size: u32,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            letters: Letters::None,
+            square_size: 32,
+        }
+    }
+}
+
+/// Font size for the letters of a square, shrunk so rebus entries fit
+fn letter_font_size(size: f64, letters: usize) -> f64 {
+    let full = size * 0.6;
+    if letters <= 1 {
+        full
+    } else {
+        // characters are about 0.6em wide; leave some room at the sides
+        full.min(size * 0.85 / (letters as f64 * 0.6))
+    }
+}
+
+impl<'a> Grid<'a> {
+    /// Renders the grid as a standalone SVG, numbered with the given clues.
+    /// Circled squares are drawn from the markup, and squares marked
+    /// incorrect or revealed get a corner mark like Across Lite's.
+    pub fn to_svg(&self, clues: &Clues, options: &SvgOptions) -> String {
+        let size = options.square_size as f64;
+        // leave room for the border to be drawn outside the outermost squares
+        let margin = 1.0;
+        let width = self.width as f64 * size + margin * 2.0;
+        let height = self.height as f64 * size + margin * 2.0;
+
+        let mut numbers = HashMap::new();
+        for clue in clues.across.iter().chain(&clues.down) {
+            numbers.insert(clue.row * self.width + clue.column, clue.clue_number);
+        }
+
+        let mut svg = String::new();
+        // writing to a String can't fail
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" font-family="Helvetica, Arial, sans-serif">"#,
+            width, height, width, height
+        );
+        let _ = writeln!(
+            svg,
+            r#"<rect x="0" y="0" width="{}" height="{}" fill="white"/>"#,
+            width, height
+        );
+
+        for index in 0..self.width * self.height {
+            let x = margin + self.col(index) as f64 * size;
+            let y = margin + self.row(index) as f64 * size;
+            let black = self
+                .get_solution_character(index)
+                .or_else(|| self.get_fill_character(index))
+                .is_some_and(Square::is_black_square);
+
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" stroke="black" stroke-width="1"/>"#,
+                x,
+                y,
+                size,
+                size,
+                if black { "black" } else { "white" }
+            );
+
+            if black {
+                continue;
+            }
+
+            let flags = self.get_flags(index);
+            if flags.is_circled() {
+                let _ = writeln!(
+                    svg,
+                    r#"<circle cx="{}" cy="{}" r="{}" fill="none" stroke="black" stroke-width="0.5"/>"#,
+                    x + size / 2.0,
+                    y + size / 2.0,
+                    size / 2.0 - 1.0
+                );
+            }
+
+            if options.letters == Letters::Fill && (flags.is_incorrect() || flags.is_revealed()) {
+                let corner = size / 4.0;
+                let _ = writeln!(
+                    svg,
+                    r#"<polygon points="{},{} {},{} {},{}" fill="{}"/>"#,
+                    x + size - corner,
+                    y,
+                    x + size,
+                    y,
+                    x + size,
+                    y + corner,
+                    if flags.is_incorrect() { "red" } else { "gold" }
+                );
+            }
+
+            if let Some(number) = numbers.get(&index) {
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="{}">{}</text>"#,
+                    x + size * 0.06,
+                    y + size * 0.3,
+                    size * 0.28,
+                    number
+                );
+            }
+
+            let letters = match options.letters {
+                Letters::None => None,
+                Letters::Fill => self.get_fill(index).filter(|fill| fill != "-"),
+                Letters::Solution => self.get_solution(index),
+            };
+
+            if let Some(letters) = letters {
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="{:.2}" text-anchor="middle">{}</text>"#,
+                    x + size / 2.0,
+                    y + size * 0.88,
+                    letter_font_size(size, letters.chars().count()),
+                    escape(&letters)
+                );
+            }
+        }
+
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="black" stroke-width="2"/>"#,
+            margin,
+            margin,
+            self.width as f64 * size,
+            self.height as f64 * size
+        );
+        svg.push_str("</svg>\n");
+
+        svg
+    }
+}
+
+impl Puzzle {
+    /// Renders the puzzle's grid as a standalone SVG. Locked puzzles can't
+    /// be drawn with their solution, which is scrambled.
+    pub fn to_svg(&self, options: &SvgOptions) -> Result<String> {
+        if options.letters == Letters::Solution {
+            self.ensure_unlocked("drawing their solution")?;
+        }
+
+        Ok(self.grid().to_svg(&self.clues, options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{letter_font_size, Letters, SvgOptions};
+    use crate::test_fixtures::read_fixture;
+    use anyhow::Result;
+
+    #[test]
+    fn test_to_svg() -> Result<()> {
+        let puzzle = read_fixture("nyt_rebus_with_notes_and_shape.puz")?;
+
+        let blank = puzzle.to_svg(&SvgOptions::default())?;
+        assert!(blank.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(blank.contains(">1</text>"));
+        assert!(blank.contains("<circle"));
+        assert!(!blank.contains(">STAR</text>"));
+
+        let solved = puzzle.to_svg(&SvgOptions {
+            letters: Letters::Solution,
+            square_size: 40,
+        })?;
+        assert!(solved.contains(">STAR</text>"));
+        roxmltree::Document::parse(&solved)?;
+        assert_eq!(
+            solved.matches("fill=\"black\"").count(),
+            puzzle.solution.matches('.').count()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_locked_svg() -> Result<()> {
+        let puzzle = read_fixture("nyt_locked.puz")?;
+        assert!(puzzle.to_svg(&SvgOptions::default()).is_ok());
+        assert!(puzzle
+            .to_svg(&SvgOptions {
+                letters: Letters::Fill,
+                ..SvgOptions::default()
+            })
+            .is_ok());
+        assert!(puzzle
+            .to_svg(&SvgOptions {
+                letters: Letters::Solution,
+                ..SvgOptions::default()
+            })
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebus_font_size() {
+        assert!(letter_font_size(32.0, 4) < letter_font_size(32.0, 1));
+        assert_eq!(letter_font_size(32.0, 0), letter_font_size(32.0, 1));
+    }
+}
@@ -0,0 +1,8 @@
+//! The puz files in test_files, shared by the tests of every module
+use crate::Puzzle;
+use anyhow::Result;
+
+pub(crate) fn read_fixture(name: &str) -> Result<Puzzle> {
+    let bytes = std::fs::read(format!("../test_files/{}", name))?;
+    Ok(Puzzle::from_puz(bytes)?)
+}
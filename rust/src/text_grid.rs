@@ -0,0 +1,277 @@
+use crate::clues::Clues;
+use crate::grid::Grid;
+use crate::square::Square;
+use crate::svg::Letters;
+use crate::Puzzle;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Characters inside each square; enough for a 3 digit clue number and a
+/// marker
+const SQUARE_WIDTH: usize = 4;
+
+const RESET: &str = "\x1b[0m";
+const ACTIVE: &str = "\x1b[30;43m";
+const WRONG: &str = "\x1b[31m";
+const BLACK: &str = "\x1b[90m";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextGridOptions {
+    pub letters: Letters,
+    /// color black, active and wrong squares with ANSI escape codes
+    pub colors: bool,
+    /// squares to highlight, such as the clue being solved
+    pub active: Vec<usize>,
+    /// color squares whose fill doesn't match the solution as wrong.
+    /// Ignored for locked puzzles, whose solution is scrambled.
+    pub check: bool,
+}
+
+impl Default for TextGridOptions {
+    fn default() -> TextGridOptions {
+        TextGridOptions {
+            letters: Letters::Fill,
+            colors: false,
+            active: vec![],
+            check: true,
+        }
+    }
+}
+
+/// Fits the letters of a square into its width, marking rebus entries
+/// that are too long with an ellipsis
+fn fit(letters: &str) -> String {
+    if letters.chars().count() <= SQUARE_WIDTH {
+        format!("{:^width$}", letters, width = SQUARE_WIDTH)
+    } else {
+        letters
+            .chars()
+            .take(SQUARE_WIDTH - 1)
+            .chain(std::iter::once('…'))
+            .collect()
+    }
+}
+
+fn border(width: usize, left: char, middle: char, right: char) -> String {
+    let segment = "─".repeat(SQUARE_WIDTH);
+    let mut line = String::new();
+    line.push(left);
+    line.push_str(&vec![segment; width].join(&middle.to_string()));
+    line.push(right);
+    line
+}
+
+impl<'a> Grid<'a> {
+    /// Draws the grid with Unicode box drawing, numbered with the given
+    /// clues. Each square shows its clue number in the top left corner, a
+    /// marker in the top right (○ for circles, + for rebus squares) and its
+    /// letters underneath.
+    pub fn to_text(&self, clues: &Clues, options: &TextGridOptions) -> String {
+        let mut numbers = HashMap::new();
+        for clue in clues.across.iter().chain(&clues.down) {
+            numbers.insert(clue.row * self.width + clue.column, clue.clue_number);
+        }
+
+        let paint = |text: String, color: Option<&str>| match color {
+            Some(color) if options.colors => format!("{}{}{}", color, text, RESET),
+            _ => text,
+        };
+
+        let mut lines = vec![border(self.width, '┌', '┬', '┐')];
+        for row in 0..self.height {
+            let mut top = String::from("│");
+            let mut bottom = String::from("│");
+
+            for column in 0..self.width {
+                let index = row * self.width + column;
+                let black = self
+                    .get_solution_character(index)
+                    .or_else(|| self.get_fill_character(index))
+                    .is_some_and(Square::is_black_square);
+
+                if black {
+                    let fill = "█".repeat(SQUARE_WIDTH);
+                    top.push_str(&paint(fill.clone(), Some(BLACK)));
+                    bottom.push_str(&paint(fill, Some(BLACK)));
+                } else {
+                    let is_rebus = self.rebus.and_then(|rebus| rebus.get(index)).is_some();
+                    let marker = if self.get_flags(index).is_circled() {
+                        '○'
+                    } else if is_rebus {
+                        '+'
+                    } else {
+                        ' '
+                    };
+                    let number = numbers
+                        .get(&index)
+                        .map(ToString::to_string)
+                        .unwrap_or_default();
+
+                    let letters = match options.letters {
+                        Letters::None => None,
+                        Letters::Fill => self.get_fill(index).filter(|fill| fill != "-"),
+                        Letters::Solution => self.get_solution(index),
+                    };
+
+                    let wrong = options.letters == Letters::Fill
+                        && (self.get_flags(index).is_incorrect()
+                            || options.check
+                                && letters
+                                    .as_ref()
+                                    .is_some_and(|fill| !self.check_answer(index, fill)));
+
+                    let color = if options.active.contains(&index) {
+                        Some(ACTIVE)
+                    } else if wrong {
+                        Some(WRONG)
+                    } else {
+                        None
+                    };
+
+                    top.push_str(&paint(
+                        format!("{:<width$}{}", number, marker, width = SQUARE_WIDTH - 1),
+                        color,
+                    ));
+                    bottom.push_str(&paint(fit(&letters.unwrap_or_default()), color));
+                }
+
+                top.push('│');
+                bottom.push('│');
+            }
+
+            lines.push(top);
+            lines.push(bottom);
+            lines.push(if row + 1 == self.height {
+                border(self.width, '└', '┴', '┘')
+            } else {
+                border(self.width, '├', '┼', '┤')
+            });
+        }
+
+        lines.join("\n") + "\n"
+    }
+}
+
+/// Draws the fill without colors, numbered from the grid itself
+impl<'a> fmt::Display for Grid<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let numbering =
+            Clues::numbering(Grid::new(self.fill, self.solution, self.width, self.height));
+
+        formatter.write_str(&self.to_text(&numbering, &TextGridOptions::default()))
+    }
+}
+
+impl Puzzle {
+    /// Draws the puzzle's grid as text, for terminals and debugging.
+    /// Locked puzzles can't be drawn with their solution, which is
+    /// scrambled.
+    pub fn to_text(&self, options: &TextGridOptions) -> Result<String> {
+        if options.letters == Letters::Solution {
+            self.ensure_unlocked("drawing their solution")?;
+        }
+
+        if options.check && self.is_locked() {
+            let options = TextGridOptions {
+                check: false,
+                ..options.clone()
+            };
+            return Ok(self.grid().to_text(&self.clues, &options));
+        }
+
+        Ok(self.grid().to_text(&self.clues, options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TextGridOptions;
+    use crate::test_fixtures::read_fixture;
+    use crate::Letters;
+    use anyhow::Result;
+
+    #[test]
+    fn test_to_text() -> Result<()> {
+        let mut puzzle = read_fixture("zack.puz")?;
+        puzzle.rebus.set(5, "RU")?;
+        let mut flags = puzzle.grid().get_flags(2);
+        flags.set_circled(true);
+        puzzle.markup.set(2, flags)?;
+        puzzle.set_rebus_fill(1, 0, "RU")?;
+        puzzle.set_rebus_fill(1, 1, "X")?;
+
+        assert_eq!(
+            puzzle.to_text(&TextGridOptions::default())?,
+            "┌────┬────┬────┬────┬────┐
+│1   │2   │3  ○│4   │████│
+│    │    │    │    │████│
+├────┼────┼────┼────┼────┤
+│5  +│    │    │    │6   │
+│ RU │ X  │    │    │    │
+├────┼────┼────┼────┼────┤
+│7   │    │    │    │    │
+│    │    │    │    │    │
+├────┼────┼────┼────┼────┤
+│8   │    │    │    │    │
+│    │    │    │    │    │
+├────┼────┼────┼────┼────┤
+│████│9   │    │    │    │
+│████│    │    │    │    │
+└────┴────┴────┴────┴────┘
+"
+        );
+
+        assert_eq!(
+            puzzle.grid().to_string(),
+            puzzle.to_text(&TextGridOptions::default())?
+        );
+
+        let colored = puzzle.to_text(&TextGridOptions {
+            letters: Letters::Fill,
+            colors: true,
+            active: vec![0],
+            check: true,
+        })?;
+        assert!(colored.contains("\x1b[30;43m1   \x1b[0m"));
+        assert!(colored.contains("\x1b[31m X  \x1b[0m"));
+
+        let solution = puzzle.to_text(&TextGridOptions {
+            letters: Letters::Solution,
+            ..TextGridOptions::default()
+        })?;
+        assert!(solution.contains("│ X  │ K  │ C  │ D  │████│"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_locked_to_text() -> Result<()> {
+        let mut puzzle = read_fixture("nyt_locked.puz")?;
+        let square = puzzle.solution.find(|square| square != '.').unwrap();
+        // any entry may be right, even one that differs from the scrambled letter
+        let letter = if &puzzle.solution[square..=square] == "A" {
+            "B"
+        } else {
+            "A"
+        };
+        puzzle.fill.replace_range(square..=square, letter);
+
+        let text = puzzle.to_text(&TextGridOptions {
+            colors: true,
+            ..TextGridOptions::default()
+        })?;
+        assert!(text.contains(&format!(" {}  ", letter)));
+        assert!(!text.contains("\x1b[31m"));
+
+        // and the scrambled solution isn't shown as the answers
+        assert!(puzzle
+            .to_text(&TextGridOptions {
+                letters: Letters::Solution,
+                ..TextGridOptions::default()
+            })
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::extension::Extension;
+use anyhow::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The solve clock, stored in the LTIM extension as "elapsed,stopped"
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "TimerFields", into = "TimerFields")]
+pub struct Timer {
+    /// time spent solving, with second precision
+    pub elapsed: Duration,
+    pub running: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TimerFields {
+    #[serde(rename = "elapsedSeconds")]
+    elapsed_seconds: u64,
+    running: bool,
+}
+
+impl From<TimerFields> for Timer {
+    fn from(fields: TimerFields) -> Timer {
+        Timer {
+            elapsed: Duration::from_secs(fields.elapsed_seconds),
+            running: fields.running,
+        }
+    }
+}
+
+impl From<Timer> for TimerFields {
+    fn from(timer: Timer) -> TimerFields {
+        TimerFields {
+            elapsed_seconds: timer.elapsed.as_secs(),
+            running: timer.running,
+        }
+    }
+}
+
+impl Timer {
+    pub fn from_extension(extension: Option<&Extension>) -> Result<Option<Timer>> {
+        let extension = match extension {
+            Some(extension) => extension,
+            None => return Ok(None),
+        };
+
+        let timer = std::str::from_utf8(&extension.bytes)
+            .map_err(|_e| Error::msg("Failed to parse LTIM extension"))?;
+
+        let (elapsed, stopped) = timer.split_once(',').ok_or_else(|| {
+            Error::msg(format!(
+                "Expected LTIM to be \"elapsed,stopped\"; received {:?}",
+                timer
+            ))
+        })?;
+
+        let elapsed = elapsed
+            .parse::<u64>()
+            .context(format!("Received non-integer elapsed time: {}", elapsed))?;
+
+        let running = match stopped {
+            "0" => true,
+            "1" => false,
+            _ => {
+                return Err(Error::msg(format!(
+                    "Expected LTIM stopped flag to be 0 or 1; received {:?}",
+                    stopped
+                )))
+            }
+        };
+
+        Ok(Some(Timer {
+            elapsed: Duration::from_secs(elapsed),
+            running,
+        }))
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        format!(
+            "{},{}",
+            self.elapsed.as_secs(),
+            if self.running { 0 } else { 1 }
+        )
+        .into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Timer;
+    use crate::extension::Extension;
+    use anyhow::Result;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_and_pack() -> Result<()> {
+        let extension = Extension::new(*b"LTIM", b"421,0".to_vec());
+
+        let timer = Timer::from_extension(Some(&extension))?.unwrap();
+        assert_eq!(timer.elapsed, Duration::from_secs(421));
+        assert!(timer.running);
+        assert_eq!(timer.pack(), b"421,0");
+
+        assert_eq!(Timer::from_extension(None)?, None);
+
+        let extension = Extension::new(*b"LTIM", b"421".to_vec());
+        assert!(Timer::from_extension(Some(&extension)).is_err());
+
+        Ok(())
+    }
+}
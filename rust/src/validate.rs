@@ -0,0 +1,361 @@
+use crate::clues::{Clues, Direction};
+use crate::extension::KNOWN_EXTENSIONS;
+use crate::grid::Grid;
+use crate::square::Square;
+use crate::Puzzle;
+use anyhow::Result;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// the puzzle is unusual, but can be solved and written as is
+    Warning,
+    /// the file is damaged or inconsistent, and `from_puz` refuses it
+    Error,
+}
+
+/// A problem found by `Puzzle::validate`. Rows and columns are 0-based, as
+/// everywhere else in the library.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Diagnostic {
+    /// a checksum stored in the file doesn't match the data it covers
+    ChecksumMismatch {
+        name: String,
+        stored: u64,
+        calculated: u64,
+    },
+    /// the number of clues doesn't match the number of words in the grid
+    ClueCountMismatch {
+        clues: usize,
+        slots: usize,
+    },
+    /// the fill or solution doesn't have a square for every cell
+    GridSizeMismatch {
+        field: &'static str,
+        length: usize,
+        expected: usize,
+    },
+    UnknownExtension {
+        code: String,
+    },
+    /// a known extension whose data couldn't be read, kept as raw bytes
+    UnreadableExtension {
+        code: String,
+        message: String,
+    },
+    /// the black squares are neither rotationally nor left-right symmetric
+    AsymmetricGrid,
+    /// a white square that's only part of one word
+    UncheckedSquare {
+        row: usize,
+        column: usize,
+    },
+    TwoLetterWord {
+        direction: Direction,
+        number: usize,
+        row: usize,
+        column: usize,
+    },
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Diagnostic::ChecksumMismatch { .. }
+            | Diagnostic::ClueCountMismatch { .. }
+            | Diagnostic::GridSizeMismatch { .. } => Severity::Error,
+            Diagnostic::UnknownExtension { .. }
+            | Diagnostic::UnreadableExtension { .. }
+            | Diagnostic::AsymmetricGrid
+            | Diagnostic::UncheckedSquare { .. }
+            | Diagnostic::TwoLetterWord { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnostic::ChecksumMismatch {
+                name,
+                stored,
+                calculated,
+            } => write!(
+                formatter,
+                "Calculated {} checksum {} does not match stored checksum {}",
+                name, calculated, stored
+            ),
+            Diagnostic::ClueCountMismatch { clues, slots } => write!(
+                formatter,
+                "The puzzle has {} clues but its grid has {} words",
+                clues, slots
+            ),
+            Diagnostic::GridSizeMismatch {
+                field,
+                length,
+                expected,
+            } => write!(
+                formatter,
+                "The {} has {} squares but the grid has {}",
+                field, length, expected
+            ),
+            Diagnostic::UnknownExtension { code } => {
+                write!(formatter, "Unknown extension {}", code)
+            }
+            Diagnostic::UnreadableExtension { code, message } => {
+                write!(
+                    formatter,
+                    "Could not read the {} extension: {}",
+                    code, message
+                )
+            }
+            Diagnostic::AsymmetricGrid => {
+                write!(formatter, "The grid is not symmetric")
+            }
+            Diagnostic::UncheckedSquare { row, column } => write!(
+                formatter,
+                "The square at row {}, column {} is only in one word",
+                row + 1,
+                column + 1
+            ),
+            Diagnostic::TwoLetterWord {
+                direction, number, ..
+            } => write!(formatter, "{} {:?} is two letters long", number, direction),
+        }
+    }
+}
+
+/// Whether the black squares look the same after turning the grid half way
+/// around or flipping it left to right
+fn is_symmetric(black: &[bool], width: usize) -> bool {
+    let rotational = black.iter().eq(black.iter().rev());
+    let mirror = black
+        .chunks(width)
+        .all(|row| row.iter().eq(row.iter().rev()));
+
+    rotational || mirror
+}
+
+impl Puzzle {
+    /// Checks the puzzle for every problem it has rather than stopping at
+    /// the first: damaged or inconsistent data, which are errors, and
+    /// construction conventions it breaks, which are warnings
+    pub fn validate(&self) -> Result<Vec<Diagnostic>> {
+        let mut diagnostics = self
+            .checksums()?
+            .into_iter()
+            .filter(|checksum| !checksum.is_valid())
+            .map(|checksum| Diagnostic::ChecksumMismatch {
+                name: checksum.name,
+                stored: checksum.stored,
+                calculated: checksum.calculated,
+            })
+            .collect::<Vec<_>>();
+
+        diagnostics.extend(
+            self.extensions
+                .iter()
+                .map(|extension| String::from_utf8_lossy(&extension.code).into_owned())
+                .filter(|code| !KNOWN_EXTENSIONS.contains(&code.as_str()))
+                .map(|code| Diagnostic::UnknownExtension { code }),
+        );
+
+        let (width, height) = (self.width(), self.height());
+        let mut sizes_match = true;
+        for (field, squares) in [("fill", &self.fill), ("solution", &self.solution)] {
+            let length = squares.chars().count();
+            if length != width * height {
+                sizes_match = false;
+                diagnostics.push(Diagnostic::GridSizeMismatch {
+                    field,
+                    length,
+                    expected: width * height,
+                });
+            }
+        }
+        // the rest of the checks need a whole grid
+        if !sizes_match {
+            return Ok(diagnostics);
+        }
+
+        let numbering = Clues::numbering(Grid::new(&self.solution, &self.solution, width, height));
+        let slots = numbering.across.len() + numbering.down.len();
+        if self.all_clues.len() != slots {
+            diagnostics.push(Diagnostic::ClueCountMismatch {
+                clues: self.all_clues.len(),
+                slots,
+            });
+        }
+
+        let black = self
+            .solution
+            .chars()
+            .map(Square::is_black_square)
+            .collect::<Vec<_>>();
+        if !is_symmetric(&black, width) {
+            diagnostics.push(Diagnostic::AsymmetricGrid);
+        }
+
+        // count the words each square is part of
+        let mut words = vec![0; width * height];
+        for (direction, clue) in numbering.in_puz_order() {
+            let step = match direction {
+                Direction::Across => 1,
+                Direction::Down => width,
+            };
+            let start = clue.row * width + clue.column;
+            for index in (start..).step_by(step).take(clue.length) {
+                words[index] += 1;
+            }
+
+            if clue.length == 2 {
+                diagnostics.push(Diagnostic::TwoLetterWord {
+                    direction,
+                    number: clue.clue_number,
+                    row: clue.row,
+                    column: clue.column,
+                });
+            }
+        }
+
+        diagnostics.extend(
+            (0..width * height)
+                .filter(|&index| !black[index] && words[index] < 2)
+                .map(|index| Diagnostic::UncheckedSquare {
+                    row: index / width,
+                    column: index % width,
+                }),
+        );
+
+        Ok(diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Severity};
+    use crate::test_fixtures::read_fixture;
+    use crate::{Direction, Puzzle};
+    use anyhow::Result;
+
+    #[test]
+    fn test_validate_fixtures() -> Result<()> {
+        let puzzle = read_fixture("washpost.puz")?;
+        assert_eq!(puzzle.validate()?, vec![]);
+
+        let diagramless = read_fixture("nyt_diagramless.puz")?;
+        assert!(diagramless
+            .validate()?
+            .iter()
+            .all(|diagnostic| diagnostic.severity() == Severity::Warning));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_warnings() -> Result<()> {
+        // the black square in the corner leaves the grid asymmetric, and the
+        // squares next to it in two letter words
+        let puzzle = Puzzle::from_xd(
+            "\n\nCAT\nAGO\nTE#\n\n\nA1. Feline\nA4. Again\nA5. Tee\n\nD1. Carts\nD2. Year\nD3. To\n",
+        )?;
+
+        let diagnostics = puzzle.validate()?;
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic::AsymmetricGrid,
+                Diagnostic::TwoLetterWord {
+                    direction: Direction::Down,
+                    number: 3,
+                    row: 0,
+                    column: 2,
+                },
+                Diagnostic::TwoLetterWord {
+                    direction: Direction::Across,
+                    number: 5,
+                    row: 2,
+                    column: 0,
+                },
+            ]
+        );
+        assert_eq!(diagnostics[1].to_string(), "3 Down is two letters long");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_errors() -> Result<()> {
+        let mut puzzle = read_fixture("washpost.puz")?;
+        let slots = puzzle.all_clues.len();
+        puzzle.all_clues.pop();
+        puzzle.header.clue_count -= 1;
+        let mut missing_clue = puzzle.to_puz()?;
+        assert!(Puzzle::from_puz(missing_clue.clone()).is_err());
+
+        // extra clues can be ignored, so they're only a warning
+        let mut puzzle = read_fixture("washpost.puz")?;
+        puzzle.all_clues.push("Extra".to_string());
+        puzzle.header.clue_count += 1;
+        let extra_clue = Puzzle::from_puz(puzzle.to_puz()?)?;
+        assert_eq!(
+            extra_clue.parse_warnings(),
+            &[Diagnostic::ClueCountMismatch {
+                clues: slots + 1,
+                slots
+            }]
+        );
+
+        let puzzle = Puzzle::from_puz_unverified(missing_clue.clone())?;
+        assert_eq!(
+            puzzle.validate()?,
+            vec![Diagnostic::ClueCountMismatch {
+                clues: slots - 1,
+                slots
+            }]
+        );
+
+        // the first letter of the solution, just after the 52 byte header
+        // that starts 2 bytes before ACROSS&DOWN
+        let header = missing_clue
+            .windows(11)
+            .position(|window| window == b"ACROSS&DOWN")
+            .unwrap()
+            - 2;
+        missing_clue[header + 52] ^= 1;
+        let errors = Puzzle::from_puz_unverified(missing_clue)?
+            .validate()?
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+            .map(|diagnostic| match diagnostic {
+                Diagnostic::ChecksumMismatch { name, .. } => name,
+                diagnostic => diagnostic.to_string(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                "global".to_string(),
+                "magic".to_string(),
+                format!(
+                    "The puzzle has {} clues but its grid has {} words",
+                    slots - 1,
+                    slots
+                ),
+            ]
+        );
+
+        let mut puzzle = read_fixture("zack.puz")?;
+        puzzle.fill.pop();
+        assert!(puzzle.validate()?.contains(&Diagnostic::GridSizeMismatch {
+            field: "fill",
+            length: 24,
+            expected: 25,
+        }));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,496 @@
+//! The xd format: "Key: value" headers, the grid and clues in blocks
+//! separated by blank lines, with each clue carrying its answer:
+//!
+//! ```text
+//! Title: Mini
+//! Rebus: 1=AND
+//! Special: circle
+//!
+//!
+//! CAt
+//! 1GO
+//! TE#
+//!
+//!
+//! A1. Feline ~ CAT
+//! A4. Et ~ ANDGO
+//! ```
+//!
+//! Rebus squares are written as keys defined in the Rebus header, and
+//! lowercase letters mark special (circled) squares. Anything after the
+//! clues is notes, kept as written, and headers the puzzle has no field for
+//! are written back as they were read.
+use crate::clues::{Clues, Direction};
+use crate::grid::Grid;
+use crate::markup::SquareFlags;
+use crate::square::Square;
+use crate::Puzzle;
+use anyhow::{Context, Error, Result};
+use std::collections::HashMap;
+
+const BLOCK: char = '#';
+const REBUS_KEYS: &str = "123456789@$%&*+?!";
+/// headers read into the puzzle's own fields, or written from them
+const KNOWN_HEADERS: [&str; 6] = ["title", "author", "copyright", "notes", "rebus", "special"];
+
+/// Parses a clue line such as "A1. Feline ~ CAT"
+fn parse_clue(line: &str) -> Option<((Direction, usize), &str, &str)> {
+    let (label, rest) = line.split_once(". ")?;
+
+    let direction = match label.chars().next()? {
+        'A' => Direction::Across,
+        'D' => Direction::Down,
+        _ => return None,
+    };
+    let number = label[1..].parse().ok()?;
+
+    let (text, answer) = match rest.rsplit_once(" ~ ") {
+        Some((text, answer)) => (text, answer.trim()),
+        None => (rest, ""),
+    };
+
+    Some(((direction, number), text.trim(), answer))
+}
+
+impl Puzzle {
+    /// Reads a puzzle in the xd format. Clue answers must match the grid.
+    pub fn from_xd(text: &str) -> Result<Puzzle> {
+        // each line with the offset it starts at, so the notes can be
+        // taken from the text as they are
+        let mut lines = text
+            .split_inclusive('\n')
+            .scan(0, |offset, line| {
+                let start = *offset;
+                *offset += line.len();
+                Some((start, line.trim_end()))
+            })
+            .peekable();
+        let skip_blank_lines = |lines: &mut std::iter::Peekable<_>| {
+            while lines
+                .next_if(|(_, line): &(usize, &str)| line.is_empty())
+                .is_some()
+            {}
+        };
+
+        skip_blank_lines(&mut lines);
+        // headers are optional, and grid rows never contain a colon
+        let mut headers = HashMap::new();
+        let mut unknown_headers = vec![];
+        while let Some((_, line)) = lines.next_if(|(_, line)| line.contains(':')) {
+            let (key, value) = line.split_once(':').expect("header has a colon");
+            let (key, value) = (key.trim(), value.trim().to_string());
+            if !KNOWN_HEADERS.contains(&key.to_lowercase().as_str()) {
+                unknown_headers.push((key.to_string(), value.clone()));
+            }
+            headers.insert(key.to_lowercase(), value);
+        }
+
+        skip_blank_lines(&mut lines);
+        let mut rows = vec![];
+        while let Some((_, line)) = lines.next_if(|(_, line)| !line.is_empty()) {
+            rows.push(line.trim());
+        }
+
+        let mut clue_lines = vec![];
+        let mut notes_start = None;
+        let mut blank_lines = 0;
+        for (offset, line) in lines {
+            if line.is_empty() {
+                blank_lines += 1;
+            } else if clue_lines.is_empty() || blank_lines < 2 {
+                clue_lines.push(line);
+                blank_lines = 0;
+            } else {
+                // anything after the clues is free-form notes
+                notes_start = Some(offset);
+                break;
+            }
+        }
+
+        let rebus_table = headers
+            .get("rebus")
+            .map(String::as_str)
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|entry| {
+                let (key, answer) = entry.split_once('=').ok_or_else(|| {
+                    Error::msg(format!(
+                        "Expected rebus entries like 1=AND; received {:?}",
+                        entry
+                    ))
+                })?;
+                let mut key_characters = key.chars();
+                match (key_characters.next(), key_characters.next()) {
+                    (Some(key), None) => Ok((key, answer.to_string())),
+                    _ => Err(Error::msg(format!(
+                        "Rebus key {:?} is not one character",
+                        key
+                    ))),
+                }
+            })
+            .collect::<Result<HashMap<char, String>>>()?;
+
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+
+        let mut solution = String::new();
+        let mut rebus = vec![];
+        let mut circles = vec![];
+        for (row, line) in rows.iter().enumerate() {
+            if line.chars().count() != width {
+                return Err(Error::msg(format!(
+                    "Row {} of the grid has {} squares; expected {}",
+                    row + 1,
+                    line.chars().count(),
+                    width
+                )));
+            }
+
+            for (column, square) in line.chars().enumerate() {
+                let index = row * width + column;
+
+                if let Some(answer) = rebus_table.get(&square) {
+                    let first = answer
+                        .chars()
+                        .next()
+                        .ok_or_else(|| Error::msg(format!("Rebus {:?} has no answer", square)))?;
+                    solution.push(first);
+                    if answer.chars().count() > 1 {
+                        rebus.push((index, answer.clone()));
+                    }
+                } else if square == BLOCK || Square::is_black_square(square) {
+                    solution.push(Square::black());
+                } else if square.is_alphabetic() || square.is_ascii_digit() {
+                    if square.is_lowercase() {
+                        circles.push(index);
+                    }
+                    solution.extend(square.to_uppercase());
+                } else {
+                    return Err(Error::msg(format!(
+                        "Square {:?} in row {} isn't a letter, block or rebus key",
+                        square,
+                        row + 1
+                    )));
+                }
+            }
+        }
+
+        let mut clue_texts = HashMap::new();
+        let mut answers = HashMap::new();
+        for line in &clue_lines {
+            let (key, text, answer) = parse_clue(line)
+                .ok_or_else(|| Error::msg(format!("Malformed xd clue {:?}", line)))?;
+            clue_texts.insert(key, text.to_string());
+            answers.insert(key, answer.to_string());
+        }
+
+        let mut puzzle_rebus = crate::Rebus::new(width * height);
+        for (index, answer) in &rebus {
+            puzzle_rebus.set(*index, answer)?;
+        }
+        let grid = Grid {
+            rebus: Some(&puzzle_rebus),
+            ..Grid::new(&solution, &solution, width, height)
+        };
+        let numbering = Clues::numbering(Grid::new(&solution, &solution, width, height));
+
+        let mut all_clues = vec![];
+        for (direction, clue) in numbering.in_puz_order() {
+            let key = (direction, clue.clue_number);
+            let text = clue_texts.get(&key).ok_or_else(|| {
+                Error::msg(format!(
+                    "Missing clue for {} {:?}",
+                    clue.clue_number, direction
+                ))
+            })?;
+
+            let expected = grid.get_answer(clue, direction);
+            match answers.get(&key) {
+                Some(answer) if !answer.is_empty() && !answer.eq_ignore_ascii_case(&expected) => {
+                    return Err(Error::msg(format!(
+                        "The answer to {} {:?} is {} but the grid has {}",
+                        clue.clue_number, direction, answer, expected
+                    )))
+                }
+                _ => {}
+            }
+
+            all_clues.push(text.clone());
+        }
+
+        if all_clues.len() != clue_texts.len() {
+            return Err(Error::msg(format!(
+                "The grid has {} clues but the xd has {}",
+                all_clues.len(),
+                clue_texts.len()
+            )));
+        }
+
+        let mut puzzle = Puzzle::new(width, height, &solution, all_clues)
+            .context("Failed to build puzzle from xd")?;
+
+        let header = |name: &str| headers.get(name).cloned().unwrap_or_default();
+        puzzle.title = header("title");
+        puzzle.author = header("author");
+        puzzle.copyright = header("copyright");
+        puzzle.notes = match notes_start {
+            // the line break that ends the file isn't part of the notes
+            Some(start) => {
+                let notes = &text[start..];
+                notes
+                    .strip_suffix("\r\n")
+                    .or_else(|| notes.strip_suffix('\n'))
+                    .unwrap_or(notes)
+                    .to_string()
+            }
+            None => header("notes"),
+        };
+        puzzle.xd_headers = unknown_headers;
+        puzzle.rebus = puzzle_rebus;
+
+        for index in circles {
+            let mut flags = SquareFlags::default();
+            flags.set_circled(true);
+            puzzle.markup.set(index, flags)?;
+        }
+
+        puzzle.upgrade_version_for_text();
+        puzzle.refresh_checksums()?;
+
+        Ok(puzzle)
+    }
+
+    /// Writes the puzzle in the xd format. The solver's fill isn't part of
+    /// the format and is dropped.
+    pub fn to_xd(&self) -> Result<String> {
+        self.ensure_unlocked("they can be written as xd")?;
+
+        let grid = self.grid();
+        let mut rebus_keys: Vec<(String, char)> = vec![];
+        // digits can be squares of their own, so they can't be keys too
+        let mut free_keys = REBUS_KEYS
+            .chars()
+            .filter(|&key| !self.solution.contains(key));
+        let mut has_circles = false;
+        let mut rows = vec![];
+
+        for row in 0..self.height() {
+            let mut line = String::new();
+
+            for column in 0..self.width() {
+                let index = row * self.width() + column;
+                let square = grid
+                    .get_solution_character(index)
+                    .ok_or_else(|| Error::msg(format!("Square {} has no solution", index)))?;
+
+                if let Some(answer) = self.rebus.get(index) {
+                    let key = match rebus_keys.iter().find(|(existing, _)| existing == answer) {
+                        Some(&(_, key)) => key,
+                        None => {
+                            let key = free_keys.next().ok_or_else(|| {
+                                Error::msg("Too many distinct rebus squares for the xd format")
+                            })?;
+                            rebus_keys.push((answer.to_string(), key));
+                            key
+                        }
+                    };
+
+                    line.push(key);
+                } else if Square::is_black_square(square) {
+                    line.push(BLOCK);
+                } else if grid.get_flags(index).is_circled() {
+                    has_circles = true;
+                    line.extend(square.to_lowercase());
+                } else {
+                    line.push(square);
+                }
+            }
+
+            rows.push(line);
+        }
+
+        let mut xd = String::new();
+        for (name, value) in [
+            ("Title", &self.title),
+            ("Author", &self.author),
+            ("Copyright", &self.copyright),
+        ] {
+            if !value.is_empty() {
+                xd.push_str(&format!("{}: {}\n", name, value));
+            }
+        }
+
+        for (name, value) in &self.xd_headers {
+            xd.push_str(&format!("{}: {}\n", name, value));
+        }
+
+        if !rebus_keys.is_empty() {
+            let table = rebus_keys
+                .iter()
+                .map(|(answer, key)| format!("{}={}", key, answer))
+                .collect::<Vec<_>>();
+            xd.push_str(&format!("Rebus: {}\n", table.join(" ")));
+        }
+
+        if has_circles {
+            xd.push_str("Special: circle\n");
+        }
+
+        xd.push_str("\n\n");
+        for row in rows {
+            xd.push_str(&row);
+            xd.push('\n');
+        }
+
+        for (prefix, direction, clues) in [
+            ('A', Direction::Across, &self.clues.across),
+            ('D', Direction::Down, &self.clues.down),
+        ] {
+            xd.push_str(if direction == Direction::Across {
+                "\n\n"
+            } else {
+                "\n"
+            });
+
+            for clue in clues {
+                xd.push_str(&format!(
+                    "{}{}. {} ~ {}\n",
+                    prefix,
+                    clue.clue_number,
+                    clue.text,
+                    grid.get_answer(clue, direction)
+                ));
+            }
+        }
+
+        if !self.notes.is_empty() {
+            xd.push_str("\n\n");
+            xd.push_str(&self.notes);
+            xd.push('\n');
+        }
+
+        Ok(xd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::read_fixture;
+    use crate::Puzzle;
+    use anyhow::Result;
+
+    /// zack.puz as xd, edited as if by hand
+    fn zack_xd(edits: &[(&str, &str)]) -> Result<String> {
+        let mut xd = read_fixture("zack.puz")?.to_xd()?;
+        for (from, to) in edits {
+            assert!(xd.contains(from), "{:?} isn't in the xd", from);
+            xd = xd.replace(from, to);
+        }
+
+        Ok(xd)
+    }
+
+    /// The rebus answer of every square, as the keys of two rebus tables
+    /// needn't match
+    fn rebus_squares(puzzle: &Puzzle) -> Vec<Option<&str>> {
+        (0..puzzle.width() * puzzle.height())
+            .map(|index| puzzle.rebus.get(index))
+            .collect()
+    }
+
+    #[test]
+    fn test_xd_round_trip() -> Result<()> {
+        let xd = zack_xd(&[])?;
+        assert!(xd.starts_with("\n\nXKCD#\nRURAL\n"));
+        assert!(xd.contains("\nA9. Fallen in Fortnite, say ~ REKT\n\nD1. "));
+
+        for entry in std::fs::read_dir("../test_files")? {
+            let puzzle = Puzzle::from_puz(std::fs::read(entry?.path())?)?;
+            if puzzle.is_locked() {
+                continue;
+            }
+
+            let reparsed = Puzzle::from_xd(&puzzle.to_xd()?)?;
+
+            assert_eq!(reparsed.title, puzzle.title);
+            assert_eq!(reparsed.notes, puzzle.notes);
+            assert_eq!(reparsed.solution, puzzle.solution);
+            assert_eq!(reparsed.all_clues, puzzle.all_clues);
+            assert_eq!(reparsed.markup, puzzle.markup);
+            assert_eq!(rebus_squares(&reparsed), rebus_squares(&puzzle));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_xd_rebus_keys() -> Result<()> {
+        // a digit in the grid is kept as itself rather than a rebus key
+        let mut puzzle = read_fixture("zack.puz")?;
+        puzzle.solution.replace_range(1..2, "1");
+        puzzle.rebus.set(5, "RU")?;
+
+        let xd = puzzle.to_xd()?;
+        assert!(xd.contains("Rebus: 2=RU\n"));
+        assert!(xd.contains("\nX1CD#\n2URAL\n"));
+
+        let reparsed = Puzzle::from_xd(&xd)?;
+        assert_eq!(reparsed.solution, "X1CD.RURALAMALEYATES.REKT");
+        assert_eq!(rebus_squares(&reparsed), rebus_squares(&puzzle));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hand_written_xd() -> Result<()> {
+        // header names are case insensitive, blocks can be '.', answers can
+        // be left off and notes can be a header
+        let puzzle = Puzzle::from_xd(&zack_xd(&[
+            ("\n\nXKCD#", "title: Zack\nNotes: A note\n\n\nXKCD."),
+            ("#REKT", ".REKT"),
+            (" ~ XKCD", ""),
+        ])?)?;
+
+        assert_eq!(puzzle.title, "Zack");
+        assert_eq!(puzzle.notes, "A note");
+        assert_eq!(puzzle.solution, "XKCD.RURALAMALEYATES.REKT");
+        assert_eq!(puzzle.all_clues, read_fixture("zack.puz")?.all_clues);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_xd_notes_and_unknown_headers() -> Result<()> {
+        let notes = "First line\r\n\r\n\r\n  Indented after two blank lines";
+        let xd = zack_xd(&[("\n\nXKCD#", "Date: 2023-01-02\nEditor: Will\n\n\nXKCD#")])?;
+        let mut puzzle = Puzzle::from_xd(&format!("{}\n\n{}\r\n", xd, notes))?;
+        assert_eq!(puzzle.notes, notes);
+        assert_eq!(
+            puzzle.xd_headers,
+            [
+                ("Date".to_string(), "2023-01-02".to_string()),
+                ("Editor".to_string(), "Will".to_string()),
+            ]
+        );
+
+        puzzle.title = "Zack".to_string();
+        let written = puzzle.to_xd()?;
+        assert!(written.starts_with("Title: Zack\nDate: 2023-01-02\nEditor: Will\n\n\n"));
+        let reparsed = Puzzle::from_xd(&written)?;
+        assert_eq!(reparsed.notes, notes);
+        assert_eq!(reparsed.xd_headers, puzzle.xd_headers);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mismatched_xd() -> Result<()> {
+        let wrong_answer = zack_xd(&[("Fortnite, say ~ REKT", "Fortnite, say ~ RECT")])?;
+        assert!(Puzzle::from_xd(&wrong_answer).is_err());
+
+        let missing_clue = zack_xd(&[("D6. In case ~ LEST\n", "")])?;
+        assert!(Puzzle::from_xd(&missing_clue).is_err());
+
+        Ok(())
+    }
+}
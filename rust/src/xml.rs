@@ -0,0 +1,9 @@
+/// Escapes text for XML (and HTML), keeping carriage returns which parsers
+/// otherwise normalize away
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\r', "&#13;")
+}
@@ -13,12 +13,81 @@ type Clues = {
   down: Array<Clue>;
 };
 
-export type Grid = Array<Array<{ black: boolean; solution: string }>>;
+export type SquareFlags = {
+  circled: boolean;
+  previouslyIncorrect: boolean;
+  incorrect: boolean;
+  revealed: boolean;
+};
+
+export type Timer = {
+  elapsedSeconds: number;
+  running: boolean;
+};
+
+export type SvgOptions = {
+  letters?: 'none' | 'fill' | 'solution';
+  squareSize?: number;
+};
+
+export type ParseOptions = {
+  verifyChecksums?: 'strict' | 'warn' | 'ignore';
+  repairChecksums?: boolean;
+};
+
+/**
+ * Thrown by every method of `Puzzle` that can fail. Errors reading a puz
+ * file say where they happened; the rest are of kind 'other'.
+ */
+export type PuzError = Error & {
+  name: 'PuzError';
+  kind:
+    | 'seekError'
+    | 'encodingNotInferred'
+    | 'truncated'
+    | 'invalidField'
+    | 'decode'
+    | 'invalidExtension'
+    | 'invalid'
+    | 'other';
+  /** the field being read, such as 'title', 'clue #12' or 'GEXT' */
+  field?: string;
+  /** bytes from the start of the file */
+  offset?: number;
+};
+
+export type HtmlOptions = {
+  answerKey?: boolean;
+};
+
+export type Grid = Array<
+  Array<{ black: boolean; solution: string; flags: SquareFlags }>
+>;
 
 export class Puzzle {
-  static async fromPuz(puzData: Uint8Array) {
+  static async fromPuz(puzData: Uint8Array, options?: ParseOptions) {
     const wasm = await import('../rust/pkg');
-    return new Puzzle(wasm.Puzzle.fromPuz(puzData));
+    return new Puzzle(wasm.Puzzle.fromPuz(puzData, options));
+  }
+
+  static async fromIpuz(ipuz: string) {
+    const wasm = await import('../rust/pkg');
+    return new Puzzle(wasm.Puzzle.fromIpuz(ipuz));
+  }
+
+  static async fromJpz(jpzData: Uint8Array) {
+    const wasm = await import('../rust/pkg');
+    return new Puzzle(wasm.Puzzle.fromJpz(jpzData));
+  }
+
+  static async fromAcrossLiteText(text: string) {
+    const wasm = await import('../rust/pkg');
+    return new Puzzle(wasm.Puzzle.fromAcrossLiteText(text));
+  }
+
+  static async fromXd(xd: string) {
+    const wasm = await import('../rust/pkg');
+    return new Puzzle(wasm.Puzzle.fromXd(xd));
   }
 
   private constructor(private puzzle: wasmType.Puzzle) {}
@@ -35,6 +104,18 @@ export class Puzzle {
     return this.puzzle.width;
   }
 
+  public get timer(): Timer | null {
+    return this.puzzle.timer;
+  }
+
+  public setTimer(elapsedSeconds: number, running: boolean) {
+    this.puzzle.setTimer(elapsedSeconds, running);
+  }
+
+  public get parseWarnings(): Array<string> {
+    return this.puzzle.parseWarnings;
+  }
+
   public get solutionState(): 'Unlocked' | 'Locked' {
     return this.puzzle.solutionState as 'Unlocked' | 'Locked';
   }
@@ -54,4 +135,68 @@ export class Puzzle {
   public getDownClue(row: number, column: number): Clue | null {
     return this.puzzle.getDownClue(row, column);
   }
+
+  public checkAnswer(row: number, column: number, answer: string): boolean {
+    return this.puzzle.checkAnswer(row, column, answer);
+  }
+
+  public setSquareFlags(row: number, column: number, flags: SquareFlags) {
+    this.puzzle.setSquareFlags(row, column, flags);
+  }
+
+  public getRebusFill(row: number, column: number): string | undefined {
+    return this.puzzle.getRebusFill(row, column);
+  }
+
+  public setRebusFill(row: number, column: number, entry: string) {
+    this.puzzle.setRebusFill(row, column, entry);
+  }
+
+  public unlock(key: number) {
+    this.puzzle.unlock(key);
+  }
+
+  public isFillCorrect(): boolean {
+    return this.puzzle.isFillCorrect();
+  }
+
+  public lock(key: number) {
+    this.puzzle.lock(key);
+  }
+
+  public findUnlockKey(): Array<{ key: number; score: number }> {
+    return this.puzzle.findUnlockKey();
+  }
+
+  public toPuz(): Uint8Array {
+    return this.puzzle.toPuz();
+  }
+
+  public toIpuz(): string {
+    return this.puzzle.toIpuz();
+  }
+
+  public toJpz(): string {
+    return this.puzzle.toJpz();
+  }
+
+  public toAcrossLiteText(): string {
+    return this.puzzle.toAcrossLiteText();
+  }
+
+  public toXd(): string {
+    return this.puzzle.toXd();
+  }
+
+  public toExolve(): string {
+    return this.puzzle.toExolve();
+  }
+
+  public toSvg(options?: SvgOptions): string {
+    return this.puzzle.toSvg(options);
+  }
+
+  public toHtml(options?: HtmlOptions): string {
+    return this.puzzle.toHtml(options);
+  }
 }
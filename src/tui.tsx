@@ -23,8 +23,14 @@ type CellProps = {
   realSolution: string;
 };
 
-function Scoreboard({ wrongCells }: { wrongCells: number }) {
-  const [elapsedSeconds, setElapsedSeconds] = useState(0);
+function Scoreboard({
+  wrongCells,
+  initialElapsedSeconds,
+}: {
+  wrongCells: number;
+  initialElapsedSeconds: number;
+}) {
+  const [elapsedSeconds, setElapsedSeconds] = useState(initialElapsedSeconds);
   const success = wrongCells === 0;
 
   useEffect(() => {
@@ -37,13 +43,15 @@ function Scoreboard({ wrongCells }: { wrongCells: number }) {
     const interval = setInterval(() => {
       const now = Date.now();
 
-      setElapsedSeconds(Math.round((now - start) / 1000));
+      setElapsedSeconds(
+        initialElapsedSeconds + Math.round((now - start) / 1000),
+      );
     }, 1000);
 
     return () => {
       clearInterval(interval);
     };
-  }, [success]);
+  }, [success, initialElapsedSeconds]);
 
   const content = success
     ? `Winnar! ${elapsedSeconds}s`
@@ -343,19 +351,22 @@ function App({
           continue;
         }
 
-        if (grid[row][column].solution !== userSolution[row][column]) {
+        if (!puzzle.checkAnswer(row, column, userSolution[row][column])) {
           cells.push({ row, column });
         }
       }
     }
 
     return cells;
-  }, [userSolution, grid]);
+  }, [userSolution, grid, puzzle]);
 
   return (
     <>
       <box bottom={6} width="75%">
-        <Scoreboard wrongCells={wrongCells.length} />
+        <Scoreboard
+          wrongCells={wrongCells.length}
+          initialElapsedSeconds={puzzle.timer?.elapsedSeconds ?? 0}
+        />
         <box
           label={puzzle.title}
           top="center"